@@ -0,0 +1,583 @@
+//! Wormhole chains exported from the mapping tools wormhole corps use, so a route can go home through the chain.
+//!
+//! The format is picked from the shape of the JSON:
+//!
+//! - Tripwire: an object with `signatures` and `wormholes`, keyed by id, as Tripwire sends them to the browser. Each
+//!   wormhole joins an `initialID` and a `secondaryID` signature, and has a `type`, `life` (`stable` or `critical`),
+//!   and `mass` (`stable`, `destab`, or `critical`). Each signature has a `signatureID`, a `systemID`, and a
+//!   `lifeLeft` time.
+//! - Pathfinder: a map export, with `systems` (each with an `id`, a `systemId`, and optionally `signatures` naming
+//!   the `connection` they lead through) and `connections` (each with a `source` and `target` map system `id`, a
+//!   `scope`, and a list of `type` flags like `wh_eol`, `wh_critical`, or `frigate`). It can be wrapped in `mapData`.
+//! - Wanderer: a list of connections, or an object with the list in `data`, each with a `solar_system_source`, a
+//!   `solar_system_target`, and the numeric `type`, `mass_status`, `time_status`, and `ship_size_type` fields.
+//!
+//! Only wormholes are loaded. Holes with an end that hasn't been scanned down yet are skipped, and so are holes that
+//! have already expired.
+
+use std::{collections::{BTreeMap, HashMap}, time::SystemTime};
+
+use serde::Deserialize;
+use serde_json::{value::RawValue, Value};
+
+use crate::{
+    diagnostics::{self, Diagnostic, ParseErrors, ParseMode},
+    systems::{self, SystemId},
+    wormhole::{MassState, ShipSize, WormholeInfo},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapperFormat {
+    Tripwire,
+    Pathfinder,
+    Wanderer,
+}
+
+impl MapperFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapperFormat::Tripwire => "Tripwire",
+            MapperFormat::Pathfinder => "Pathfinder",
+            MapperFormat::Wanderer => "Wanderer",
+        }
+    }
+
+    /// Picks the format from the shape of an export.
+    pub fn detect(doc: &Value) -> Option<Self> {
+        let doc = doc.get("mapData").unwrap_or(doc);
+
+        if doc.get("signatures").is_some() && doc.get("wormholes").is_some() {
+            return Some(MapperFormat::Tripwire);
+        }
+
+        if doc.get("systems").is_some() && doc.get("connections").is_some() {
+            return Some(MapperFormat::Pathfinder);
+        }
+
+        let connections = doc.get("data").unwrap_or(doc).as_array()?;
+
+        if connections.iter().all(|c| c.get("solar_system_source").is_some()) {
+            return Some(MapperFormat::Wanderer);
+        }
+
+        None
+    }
+}
+
+/// One wormhole in a chain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainWormhole {
+    pub a: SystemId,
+    pub b: SystemId,
+    /// What is known about the jump from `a` to `b`, with the signature in `a`
+    pub a_to_b: WormholeInfo,
+    /// What is known about the jump back, with the signature in `b`
+    pub b_to_a: WormholeInfo,
+}
+
+impl ChainWormhole {
+    /// Whether either end says the hole has collapsed by `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        [&self.a_to_b, &self.b_to_a].iter().any(|info| info.expires.is_some_and(|expires| expires <= now))
+    }
+}
+
+/// An id, which the mappers write as a number or a string.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Id {
+    Number(i64),
+    Text(String),
+}
+
+impl Id {
+    fn get(&self) -> Option<i64> {
+        match self {
+            Id::Number(id) => Some(*id),
+            Id::Text(id) => id.trim().parse().ok(),
+        }
+    }
+}
+
+/// Like `ABC-123`, however the mapper writes it.
+fn format_signature(signature: &str) -> Option<String> {
+    let signature = signature.trim().to_uppercase();
+
+    match signature.len() {
+        0 => None,
+        6 if signature.chars().all(|c| c.is_ascii_alphanumeric()) => Some(format!("{}-{}", &signature[..3], &signature[3..])),
+        _ => Some(signature),
+    }
+}
+
+/// The system with this id, or None if the end hasn't been scanned down.
+fn get_system(id: Option<&Id>) -> Result<Option<SystemId>, String> {
+    let Some(id) = id.and_then(Id::get).filter(|id| *id > 0) else {
+        return Ok(None);
+    };
+
+    match systems::get_system_index(id) {
+        Some(_) => Ok(Some(id)),
+        None => Err(format!("could not find system {id}")),
+    }
+}
+
+fn offset_of(content: &str, raw: &RawValue) -> usize {
+    raw.get().as_ptr() as usize - content.as_ptr() as usize
+}
+
+/// Parses every entry, keeping the ones that aren't None, and pointing errors at the entry they're about.
+fn parse_entries<'a, T: Deserialize<'a>, R>(
+    file: &str,
+    content: &str,
+    entries: impl IntoIterator<Item = &'a RawValue>,
+    mut parse: impl FnMut(T) -> Result<Option<R>, String>,
+    parsed: &mut Vec<R>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    for raw in entries {
+        let result = serde_json::from_str::<T>(raw.get())
+            .map_err(|e| e.to_string())
+            .and_then(&mut parse);
+
+        match result {
+            Ok(value) => parsed.extend(value),
+            Err(reason) => diagnostics.push(Diagnostic::at_offset(file, content, offset_of(content, raw), reason)),
+        }
+    }
+}
+
+/// The entries of a JSON object keyed by id, in the order they're written.
+fn get_ordered<'a>(content: &str, entries: BTreeMap<String, &'a RawValue>) -> Vec<&'a RawValue> {
+    let mut entries = entries.into_values().collect::<Vec<_>>();
+
+    entries.sort_by_key(|raw| offset_of(content, raw));
+
+    entries
+}
+
+#[derive(Debug, Deserialize)]
+struct TripwireFile<'a> {
+    #[serde(borrow)]
+    signatures: BTreeMap<String, &'a RawValue>,
+    #[serde(borrow)]
+    wormholes: BTreeMap<String, &'a RawValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TripwireSignature {
+    id: Id,
+    #[serde(rename = "signatureID")]
+    signature_id: Option<String>,
+    #[serde(rename = "systemID")]
+    system_id: Option<Id>,
+    life_left: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TripwireWormhole {
+    #[serde(rename = "initialID")]
+    initial_id: Option<Id>,
+    #[serde(rename = "secondaryID")]
+    secondary_id: Option<Id>,
+    #[serde(rename = "type")]
+    wh_type: Option<String>,
+    life: Option<String>,
+    mass: Option<String>,
+}
+
+fn parse_tripwire(file: &str, content: &str, diagnostics: &mut Vec<Diagnostic>) -> Result<Vec<ChainWormhole>, Diagnostic> {
+    let doc = serde_json::from_str::<TripwireFile>(content).map_err(|e| Diagnostic::at_json_error(file, content, &e))?;
+
+    // (system, signature, expiry) by signature id
+    let mut signatures = HashMap::new();
+
+    let mut parsed = Vec::new();
+
+    parse_entries(file, content, get_ordered(content, doc.signatures), |sig: TripwireSignature| {
+        let expires = sig.life_left.as_deref()
+            .filter(|t| !t.trim().is_empty())
+            .map(|t| humantime::parse_rfc3339_weak(t.trim()).map_err(|e| format!("invalid lifeLeft '{t}' ({e})")))
+            .transpose()?;
+
+        Ok(Some((sig.id.get(), get_system(sig.system_id.as_ref())?, sig.signature_id.as_deref().and_then(format_signature), expires)))
+    }, &mut parsed, diagnostics);
+
+    for (id, system, signature, expires) in parsed {
+        if let Some(id) = id {
+            signatures.insert(id, (system, signature, expires));
+        }
+    }
+
+    let mut wormholes = Vec::new();
+
+    parse_entries(file, content, get_ordered(content, doc.wormholes), |wh: TripwireWormhole| {
+        let end = |id: Option<&Id>| id.and_then(Id::get).and_then(|id| signatures.get(&id));
+
+        let (Some((Some(a), a_sig, a_expires)), Some((Some(b), b_sig, b_expires))) = (end(wh.initial_id.as_ref()), end(wh.secondary_id.as_ref())) else {
+            return Ok(None);
+        };
+
+        let mass = wh.mass.as_deref()
+            .map(|m| MassState::from_tag(m).ok_or_else(|| format!("unknown mass '{m}'")))
+            .transpose()?;
+
+        let info = WormholeInfo {
+            wh_type: wh.wh_type.filter(|t| !t.trim().is_empty() && t != "????"),
+            expires: a_expires.or(*b_expires),
+            eol: wh.life.as_deref() == Some("critical"),
+            mass,
+            ..Default::default()
+        };
+
+        Ok(Some(ChainWormhole {
+            a: *a,
+            b: *b,
+            a_to_b: WormholeInfo { signature: a_sig.clone(), ..info.clone() },
+            b_to_a: WormholeInfo { signature: b_sig.clone(), ..info },
+        }))
+    }, &mut wormholes, diagnostics);
+
+    Ok(wormholes)
+}
+
+#[derive(Debug, Deserialize)]
+struct PathfinderWrapper<'a> {
+    #[serde(rename = "mapData", borrow)]
+    map_data: &'a RawValue,
+}
+
+#[derive(Debug, Deserialize)]
+struct PathfinderFile<'a> {
+    #[serde(borrow)]
+    systems: Vec<&'a RawValue>,
+    #[serde(borrow)]
+    connections: Vec<&'a RawValue>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PathfinderSystem {
+    id: Id,
+    system_id: Option<Id>,
+    #[serde(default)]
+    signatures: Vec<PathfinderSignature>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PathfinderSignature {
+    name: Option<String>,
+    connection: Option<PathfinderLink>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PathfinderLink {
+    id: Option<Id>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PathfinderConnection {
+    id: Option<Id>,
+    source: Id,
+    target: Id,
+    scope: Option<String>,
+    #[serde(rename = "type", default)]
+    flags: Vec<String>,
+}
+
+/// Fills in what a Pathfinder connection flag says.
+fn apply_pathfinder_flag(info: &mut WormholeInfo, flag: &str) {
+    match flag {
+        "wh_fresh" => info.mass = Some(MassState::Fresh),
+        "wh_reduced" => info.mass = Some(MassState::Reduced),
+        "wh_critical" => info.mass = Some(MassState::Critical),
+        "wh_eol" => info.eol = true,
+        "frigate" | "wh_jump_mass_s" => info.size = Some(ShipSize::Small),
+        "wh_jump_mass_m" => info.size = Some(ShipSize::Medium),
+        "wh_jump_mass_l" => info.size = Some(ShipSize::Large),
+        "wh_jump_mass_xl" => info.size = Some(ShipSize::Xl),
+        _ => {},
+    }
+}
+
+fn parse_pathfinder(file: &str, content: &str, diagnostics: &mut Vec<Diagnostic>) -> Result<Vec<ChainWormhole>, Diagnostic> {
+    let map = match serde_json::from_str::<PathfinderWrapper>(content) {
+        Ok(wrapper) => wrapper.map_data.get(),
+        Err(_) => content,
+    };
+
+    let doc = serde_json::from_str::<PathfinderFile>(map).map_err(|e| Diagnostic::at_json_error_in(file, content, map, &e))?;
+
+    let mut systems = Vec::new();
+
+    parse_entries(file, content, doc.systems, |sys: PathfinderSystem| {
+        Ok(Some((sys.id.get(), get_system(sys.system_id.as_ref())?, sys.signatures)))
+    }, &mut systems, diagnostics);
+
+    // the system each map id is, and the signature in it that leads through each connection
+    let mut by_map_id = HashMap::new();
+    let mut signatures = HashMap::new();
+
+    for (map_id, system, sigs) in systems {
+        let Some(map_id) = map_id else { continue };
+
+        by_map_id.insert(map_id, system);
+
+        for sig in sigs {
+            let connection = sig.connection.and_then(|c| c.id).and_then(|id| id.get());
+
+            if let (Some(connection), Some(name)) = (connection, sig.name.as_deref().and_then(format_signature)) {
+                signatures.insert((map_id, connection), name);
+            }
+        }
+    }
+
+    let mut wormholes = Vec::new();
+
+    parse_entries(file, content, doc.connections, |conn: PathfinderConnection| {
+        if conn.scope.as_deref().is_some_and(|s| s != "wh") {
+            return Ok(None);
+        }
+
+        let (Some(source), Some(target)) = (conn.source.get(), conn.target.get()) else {
+            return Err("expected map system ids in 'source' and 'target'".to_owned());
+        };
+
+        let end = |map_id: i64| by_map_id.get(&map_id).copied().ok_or_else(|| format!("no map system with id {map_id}"));
+
+        let (Some(a), Some(b)) = (end(source)?, end(target)?) else {
+            return Ok(None);
+        };
+
+        let mut info = WormholeInfo::default();
+
+        for flag in &conn.flags {
+            apply_pathfinder_flag(&mut info, flag);
+        }
+
+        let signature = |map_id: i64| conn.id.as_ref().and_then(Id::get).and_then(|id| signatures.get(&(map_id, id)).cloned());
+
+        Ok(Some(ChainWormhole {
+            a,
+            b,
+            a_to_b: WormholeInfo { signature: signature(source), ..info.clone() },
+            b_to_a: WormholeInfo { signature: signature(target), ..info },
+        }))
+    }, &mut wormholes, diagnostics);
+
+    Ok(wormholes)
+}
+
+#[derive(Debug, Deserialize)]
+struct WandererWrapper<'a> {
+    #[serde(borrow)]
+    data: Vec<&'a RawValue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WandererConnection {
+    solar_system_source: Option<Id>,
+    solar_system_target: Option<Id>,
+    #[serde(rename = "type", default)]
+    kind: i64,
+    #[serde(default)]
+    mass_status: i64,
+    #[serde(default)]
+    time_status: i64,
+    ship_size_type: Option<i64>,
+}
+
+fn parse_wanderer(file: &str, content: &str, diagnostics: &mut Vec<Diagnostic>) -> Result<Vec<ChainWormhole>, Diagnostic> {
+    let entries = match serde_json::from_str::<WandererWrapper>(content) {
+        Ok(wrapper) => wrapper.data,
+        Err(_) => serde_json::from_str::<Vec<&RawValue>>(content).map_err(|e| Diagnostic::at_json_error(file, content, &e))?,
+    };
+
+    let mut wormholes = Vec::new();
+
+    parse_entries(file, content, entries, |conn: WandererConnection| {
+        // 0 is a wormhole, anything else is a gate or a bridge
+        if conn.kind != 0 {
+            return Ok(None);
+        }
+
+        let (Some(a), Some(b)) = (get_system(conn.solar_system_source.as_ref())?, get_system(conn.solar_system_target.as_ref())?) else {
+            return Ok(None);
+        };
+
+        let mass = match conn.mass_status {
+            0 => MassState::Fresh,
+            1 => MassState::Reduced,
+            2 => MassState::Critical,
+            other => return Err(format!("unknown mass_status {other}")),
+        };
+
+        let size = conn.ship_size_type
+            .map(|size| match size {
+                0 => Ok(ShipSize::Small),
+                1 => Ok(ShipSize::Medium),
+                2 => Ok(ShipSize::Large),
+                3 => Ok(ShipSize::Xl),
+                other => Err(format!("unknown ship_size_type {other}")),
+            })
+            .transpose()?;
+
+        let info = WormholeInfo {
+            size,
+            eol: conn.time_status == 1,
+            mass: Some(mass),
+            ..Default::default()
+        };
+
+        Ok(Some(ChainWormhole { a, b, a_to_b: info.clone(), b_to_a: info }))
+    }, &mut wormholes, diagnostics);
+
+    Ok(wormholes)
+}
+
+/// Parses a chain exported from Tripwire, Pathfinder, or Wanderer, picking the format from its shape.
+///
+/// Holes that have expired by `now` are left out. `file` is only used to label diagnostics. Returns the format, and
+/// the entries that were skipped in lenient mode.
+pub fn parse_chain(file: &str, content: &str, mode: ParseMode, now: SystemTime) -> Result<(MapperFormat, Vec<ChainWormhole>, Vec<Diagnostic>), ParseErrors> {
+    let doc = serde_json::from_str::<Value>(content).map_err(|e| ParseErrors(vec![Diagnostic::at_json_error(file, content, &e)]))?;
+
+    let format = MapperFormat::detect(&doc).ok_or_else(|| {
+        ParseErrors(vec![Diagnostic::at_offset(file, content, 0, "expected a Tripwire, Pathfinder, or Wanderer export")])
+    })?;
+
+    let mut diagnostics = Vec::new();
+
+    let wormholes = match format {
+        MapperFormat::Tripwire => parse_tripwire(file, content, &mut diagnostics),
+        MapperFormat::Pathfinder => parse_pathfinder(file, content, &mut diagnostics),
+        MapperFormat::Wanderer => parse_wanderer(file, content, &mut diagnostics),
+    }
+    .map_err(|e| ParseErrors(vec![e]))?;
+
+    let wormholes = wormholes.into_iter().filter(|hole| !hole.is_expired(now)).collect();
+
+    let (wormholes, warnings) = diagnostics::finish(wormholes, diagnostics, mode)?;
+
+    Ok((format, wormholes, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JITA: SystemId = 30000142;
+    const THERA: SystemId = 31000005;
+
+    fn now() -> SystemTime {
+        humantime::parse_rfc3339("2024-05-01T12:00:00Z").unwrap()
+    }
+
+    fn parse(content: &str) -> (MapperFormat, Vec<ChainWormhole>) {
+        let (format, wormholes, _) = parse_chain("chain.json", content, ParseMode::Strict, now()).unwrap();
+
+        (format, wormholes)
+    }
+
+    #[test]
+    fn reads_tripwire() {
+        let (format, wormholes) = parse(r#"{
+            "signatures": {
+                "1": {"id": "1", "signatureID": "abc123", "systemID": "30000142", "lifeLeft": "2024-05-01 18:00:00"},
+                "2": {"id": "2", "signatureID": "xyz789", "systemID": "31000005"},
+                "3": {"id": "3", "signatureID": "def456", "systemID": "31000005"},
+                "4": {"id": "4", "signatureID": "", "systemID": null}
+            },
+            "wormholes": {
+                "9": {"initialID": "1", "secondaryID": "2", "type": "B274", "life": "critical", "mass": "destab"},
+                "10": {"initialID": "3", "secondaryID": "4", "type": "K162", "life": "stable", "mass": "stable"}
+            }
+        }"#);
+
+        assert_eq!(format, MapperFormat::Tripwire);
+        assert_eq!(wormholes.len(), 1);
+
+        let hole = &wormholes[0];
+
+        assert_eq!((hole.a, hole.b), (JITA, THERA));
+        assert_eq!(hole.a_to_b.signature.as_deref(), Some("ABC-123"));
+        assert_eq!(hole.b_to_a.signature.as_deref(), Some("XYZ-789"));
+        assert_eq!(hole.a_to_b.wh_type.as_deref(), Some("B274"));
+        assert!(hole.a_to_b.eol);
+        assert_eq!(hole.a_to_b.mass, Some(MassState::Reduced));
+        assert!(hole.b_to_a.expires.is_some());
+    }
+
+    #[test]
+    fn expired_holes_are_left_out() {
+        let (_, wormholes) = parse(r#"{
+            "signatures": {
+                "1": {"id": "1", "signatureID": "abc123", "systemID": "30000142", "lifeLeft": "2024-05-01 11:00:00"},
+                "2": {"id": "2", "signatureID": "xyz789", "systemID": "31000005"},
+                "3": {"id": "3", "signatureID": "def456", "systemID": "30000142", "lifeLeft": "2024-05-01 13:00:00"},
+                "4": {"id": "4", "signatureID": "ghi012", "systemID": "31000005"}
+            },
+            "wormholes": {
+                "9": {"initialID": "1", "secondaryID": "2", "type": "B274"},
+                "10": {"initialID": "3", "secondaryID": "4", "type": "B274"}
+            }
+        }"#);
+
+        assert_eq!(wormholes.len(), 1);
+        assert_eq!(wormholes[0].a_to_b.signature.as_deref(), Some("DEF-456"));
+    }
+
+    #[test]
+    fn reads_pathfinder() {
+        let (format, wormholes) = parse(r#"{"mapData": {
+            "systems": [
+                {"id": 1, "systemId": 30000142, "signatures": [{"name": "ABC-123", "connection": {"id": 7}}]},
+                {"id": 2, "systemId": 31000005}
+            ],
+            "connections": [
+                {"id": 7, "source": 1, "target": 2, "scope": "wh", "type": ["wh_critical", "wh_eol", "frigate"]},
+                {"id": 8, "source": 1, "target": 2, "scope": "stargate", "type": []}
+            ]
+        }}"#);
+
+        assert_eq!(format, MapperFormat::Pathfinder);
+        assert_eq!(wormholes.len(), 1);
+
+        let hole = &wormholes[0];
+
+        assert_eq!((hole.a, hole.b), (JITA, THERA));
+        assert_eq!(hole.a_to_b.signature.as_deref(), Some("ABC-123"));
+        assert_eq!(hole.b_to_a.signature, None);
+        assert_eq!((hole.a_to_b.size, hole.a_to_b.mass, hole.a_to_b.eol), (Some(ShipSize::Small), Some(MassState::Critical), true));
+    }
+
+    #[test]
+    fn reads_wanderer() {
+        let (format, wormholes) = parse(r#"{"data": [
+            {"solar_system_source": 30000142, "solar_system_target": 31000005, "type": 0, "mass_status": 2, "time_status": 1, "ship_size_type": 1},
+            {"solar_system_source": 30000142, "solar_system_target": 30000144, "type": 1}
+        ]}"#);
+
+        assert_eq!(format, MapperFormat::Wanderer);
+        assert_eq!(wormholes.len(), 1);
+        assert_eq!((wormholes[0].a_to_b.size, wormholes[0].a_to_b.mass), (Some(ShipSize::Medium), Some(MassState::Critical)));
+    }
+
+    #[test]
+    fn bad_entries_point_at_the_entry() {
+        let errors = parse_chain("chain.json", "[\n  {\"solar_system_source\": 30000142, \"solar_system_target\": 1}\n]", ParseMode::Strict, now()).unwrap_err();
+
+        assert_eq!((errors.0[0].line, errors.0[0].column), (2, 3));
+        assert_eq!(errors.0[0].reason, "could not find system 1");
+
+        // errors inside a mapData wrapper point into the whole file
+        let errors = parse_chain("chain.json", "{\"mapData\": {\"systems\": [], \"connections\": 5}}", ParseMode::Strict, now()).unwrap_err();
+
+        assert_eq!((errors.0[0].line, errors.0[0].column), (1, 44));
+
+        let errors = parse_chain("chain.json", "{\"systems\": []}", ParseMode::Strict, now()).unwrap_err();
+
+        assert_eq!(errors.0[0].reason, "expected a Tripwire, Pathfinder, or Wanderer export");
+    }
+}
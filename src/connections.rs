@@ -0,0 +1,559 @@
+//! Extra jumps from a connections file, for anything the bookmark and ansiblex formats don't cover, like filament
+//! shortcuts, jump bridges exported from other tools, or temporary links.
+//!
+//! Connections can be written as TOML, with one table per connection:
+//!
+//! ```toml
+//! [[connection]]
+//! from = "Jita"
+//! to = "Amarr"
+//! kind = "custom"                     # optional: custom (the default), wormhole, gate, ansiblex, or cyno
+//! one_way = true                      # optional, connections go both ways by default
+//! cost = 2.5                          # optional, replaces the cost of the kind for this connection only
+//! expires = "2024-05-01T18:00:00Z"    # optional, the connection is ignored after this
+//! note = "filament"                   # optional, shown next to the jump
+//! size = "large"                      # optional, the biggest ships a wormhole lets through: small, medium, large, or xl
+//! mass = "reduced"                    # optional, how much mass a wormhole has left: fresh, reduced, or critical
+//! ```
+//!
+//! Or as CSV, with a header naming the columns. Only `from` and `to` are required, and empty fields are left out:
+//!
+//! ```csv
+//! from,to,kind,one_way,cost,expires,note,size,mass
+//! Jita,Amarr,custom,true,2.5,2024-05-01T18:00:00Z,filament,,
+//! ```
+
+use std::{path::Path, time::SystemTime};
+
+use itertools::Itertools;
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::{
+    diagnostics::{self, Diagnostic, LineError, ParseErrors, ParseMode},
+    find_system_by_name,
+    search::Cost,
+    systems::SystemId,
+    wormhole::{MassState, ShipSize, WormholeInfo},
+    Jump,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionFormat {
+    Toml,
+    Csv,
+}
+
+impl ConnectionFormat {
+    /// Picks the format from a file's extension, which is TOML unless it's `.csv`.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => ConnectionFormat::Csv,
+            _ => ConnectionFormat::Toml,
+        }
+    }
+}
+
+/// One entry in a connections file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Connection {
+    pub from: SystemId,
+    pub to: SystemId,
+    pub kind: Jump,
+    /// Only from `from` to `to`, instead of both ways
+    pub one_way: bool,
+    /// Replaces the cost of `kind`, for this connection only
+    pub cost: Option<Cost>,
+    pub expires: Option<SystemTime>,
+    pub note: Option<String>,
+    pub size: Option<ShipSize>,
+    pub mass: Option<MassState>,
+}
+
+impl Connection {
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// What is known about the wormhole, for wormholes and anything with a size or mass state.
+    pub fn get_wormhole_info(&self) -> Option<WormholeInfo> {
+        if self.kind != Jump::Wormhole && self.size.is_none() && self.mass.is_none() {
+            return None;
+        }
+
+        Some(WormholeInfo {
+            size: self.size,
+            expires: self.expires,
+            mass: self.mass,
+            ..Default::default()
+        })
+        .filter(|info| info.is_known())
+    }
+}
+
+/// Parses a connections file. Connections that expired before `now` are left out, each with a warning.
+///
+/// `file` is only used to label diagnostics. Returns the entries that were skipped in lenient mode, and the expired
+/// ones in either mode.
+pub fn parse_connections(
+    file: &str,
+    content: &str,
+    format: ConnectionFormat,
+    mode: ParseMode,
+    now: SystemTime,
+) -> Result<(Vec<Connection>, Vec<Diagnostic>), ParseErrors> {
+    match format {
+        ConnectionFormat::Toml => parse_toml(file, content, mode, now),
+        ConnectionFormat::Csv => parse_csv(file, content, mode, now),
+    }
+}
+
+fn parse_kind(kind: &str) -> Result<Jump, String> {
+    let kind = kind.trim().to_lowercase();
+
+    Jump::ALL.into_iter()
+        .find(|j| j.name() == kind)
+        .ok_or_else(|| format!("unknown kind '{kind}': expected one of {}", Jump::ALL.iter().map(|j| j.name()).join(", ")))
+}
+
+fn check_cost(cost: Cost) -> Result<Cost, String> {
+    if !cost.is_finite() || cost < 0.0 {
+        return Err(format!("costs must be zero or positive, got {cost}"));
+    }
+
+    Ok(cost)
+}
+
+fn parse_size(size: &str) -> Result<ShipSize, String> {
+    ShipSize::from_tag(size.trim())
+        .ok_or_else(|| format!("unknown size '{size}': expected one of {}", ShipSize::ALL.iter().map(|s| s.name()).join(", ")))
+}
+
+fn parse_mass(mass: &str) -> Result<MassState, String> {
+    MassState::from_tag(mass.trim())
+        .ok_or_else(|| format!("unknown mass state '{mass}': expected one of {}", MassState::ALL.iter().map(|m| m.name()).join(", ")))
+}
+
+/// Why an expired connection was left out.
+fn describe_expired(expires: SystemTime) -> String {
+    format!("expired at {}, so it's left out", humantime::format_rfc3339_seconds(expires))
+}
+
+fn parse_expiry(expires: &str) -> Result<SystemTime, String> {
+    humantime::parse_rfc3339_weak(expires.trim())
+        .map_err(|e| format!("invalid expiry time '{expires}' ({e}): expected a UTC time like 2024-05-01T18:00:00Z"))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlFile {
+    #[serde(default)]
+    connection: Vec<TomlConnection>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TomlConnection {
+    from: Spanned<String>,
+    to: Spanned<String>,
+    kind: Option<Spanned<String>>,
+    #[serde(default)]
+    one_way: bool,
+    cost: Option<Spanned<Cost>>,
+    expires: Option<Spanned<String>>,
+    note: Option<String>,
+    size: Option<Spanned<String>>,
+    mass: Option<Spanned<String>>,
+}
+
+fn parse_toml(file: &str, content: &str, mode: ParseMode, now: SystemTime) -> Result<(Vec<Connection>, Vec<Diagnostic>), ParseErrors> {
+    let doc = toml::from_str::<TomlFile>(content).map_err(|e| {
+        let offset = e.span().map(|s| s.start).unwrap_or_default();
+
+        ParseErrors(vec![Diagnostic::at_offset(file, content, offset, e.message())])
+    })?;
+
+    let mut connections = Vec::new();
+    let mut diagnostics = Vec::new();
+    let mut expired = Vec::new();
+
+    for entry in doc.connection {
+        // points the error at the value it's about
+        let at = |span: std::ops::Range<usize>, reason: String| Diagnostic::at_offset(file, content, span.start, reason);
+
+        let from = find_system_by_name(entry.from.get_ref()).map_err(|e| at(entry.from.span(), e.to_string()));
+        let to = find_system_by_name(entry.to.get_ref()).map_err(|e| at(entry.to.span(), e.to_string()));
+
+        let kind = entry.kind.as_ref()
+            .map(|k| parse_kind(k.get_ref()).map_err(|e| at(k.span(), e)))
+            .transpose();
+
+        let cost = entry.cost.as_ref()
+            .map(|c| check_cost(*c.get_ref()).map_err(|e| at(c.span(), e)))
+            .transpose();
+
+        let expires = entry.expires.as_ref()
+            .map(|x| parse_expiry(x.get_ref()).map_err(|e| at(x.span(), e)))
+            .transpose();
+
+        let size = entry.size.as_ref()
+            .map(|s| parse_size(s.get_ref()).map_err(|e| at(s.span(), e)))
+            .transpose();
+
+        let mass = entry.mass.as_ref()
+            .map(|m| parse_mass(m.get_ref()).map_err(|e| at(m.span(), e)))
+            .transpose();
+
+        match (from, to, kind, cost, expires, size, mass) {
+            (Ok(_), Ok(_), Ok(_), Ok(_), Ok(Some(expires)), Ok(_), Ok(_)) if expires <= now => {
+                let span = entry.expires.as_ref().map(|x| x.span()).unwrap_or_default();
+
+                expired.push(at(span, describe_expired(expires)));
+            },
+            (Ok(from), Ok(to), Ok(kind), Ok(cost), Ok(expires), Ok(size), Ok(mass)) => connections.push(Connection {
+                from,
+                to,
+                kind: kind.unwrap_or(Jump::Custom),
+                one_way: entry.one_way,
+                cost,
+                expires,
+                note: entry.note,
+                size,
+                mass,
+            }),
+            (from, to, kind, cost, expires, size, mass) => {
+                // report the first problem with each entry, like a bad line in the other formats
+                let err = [from.err(), to.err(), kind.err(), cost.err(), expires.err(), size.err(), mass.err()].into_iter().flatten().next();
+
+                diagnostics.extend(err);
+            },
+        }
+    }
+
+    let (connections, mut warnings) = diagnostics::finish(connections, diagnostics, mode)?;
+
+    warnings.extend(expired);
+
+    Ok((connections, warnings))
+}
+
+/// The columns a CSV connections file can have.
+const CSV_COLUMNS: [&str; 9] = ["from", "to", "kind", "one_way", "cost", "expires", "note", "size", "mass"];
+
+/// Splits a CSV line into `(raw field, value)`. Fields can be quoted, with `""` for a literal quote, but can't span
+/// lines.
+fn split_csv_line(line: &str) -> Result<Vec<(&str, String)>, LineError> {
+    let mut fields = Vec::new();
+    let mut rest = line;
+
+    loop {
+        let field = rest.trim_start();
+
+        let Some(quoted) = field.strip_prefix('"') else {
+            let (value, next) = match field.split_once(',') {
+                Some((value, next)) => (value, Some(next)),
+                None => (field, None),
+            };
+
+            fields.push((value.trim_end(), value.trim_end().to_owned()));
+
+            match next {
+                Some(next) => rest = next,
+                None => return Ok(fields),
+            }
+
+            continue;
+        };
+
+        let mut value = String::new();
+        let mut end = None;
+        let mut chars = quoted.char_indices().peekable();
+
+        while let Some((idx, c)) = chars.next() {
+            if c != '"' {
+                value.push(c);
+            } else if chars.next_if(|(_, c)| *c == '"').is_some() {
+                value.push('"');
+            } else {
+                end = Some(idx + 1);
+                break;
+            }
+        }
+
+        let end = end.ok_or_else(|| LineError::at(line, field, "unterminated quote"))?;
+
+        fields.push((&field[..end + 1], value));
+
+        let after = quoted[end..].trim_start();
+
+        match after.strip_prefix(',') {
+            Some(next) => rest = next,
+            None if after.is_empty() => return Ok(fields),
+            None => return Err(LineError::at(line, after, "expected ',' after a quoted field")),
+        }
+    }
+}
+
+fn parse_csv_header(line: &str) -> Result<Vec<String>, LineError> {
+    let header = split_csv_line(line)?;
+
+    for (raw, name) in &header {
+        if !CSV_COLUMNS.contains(&name.to_lowercase().as_str()) {
+            return Err(LineError::at(line, raw, format!("unknown column '{name}': expected {}", CSV_COLUMNS.join(", "))));
+        }
+    }
+
+    let names = header.into_iter().map(|(_, name)| name.to_lowercase()).collect_vec();
+
+    for required in ["from", "to"] {
+        if !names.iter().any(|n| n == required) {
+            return Err(LineError::at_end(line, format!("missing the '{required}' column")));
+        }
+    }
+
+    Ok(names)
+}
+
+/// Parses a row, along with its raw `expires` field so that an expired row can be pointed at.
+fn parse_csv_row<'a>(line: &'a str, header: &[String]) -> Result<(Connection, Option<&'a str>), LineError> {
+    let fields = split_csv_line(line)?;
+
+    if fields.len() > header.len() {
+        return Err(LineError::at(line, fields[header.len()].0, format!("expected {} columns, found {}", header.len(), fields.len())));
+    }
+
+    // a field that was left out or left empty
+    let get = |column: &str| {
+        header.iter()
+            .position(|n| n == column)
+            .and_then(|idx| fields.get(idx))
+            .filter(|(_, value)| !value.trim().is_empty())
+            .map(|(raw, value)| (*raw, value.trim()))
+    };
+
+    let system = |column: &str| -> Result<SystemId, LineError> {
+        let (raw, name) = get(column).ok_or_else(|| LineError::at_end(line, format!("expected a system in the '{column}' column")))?;
+
+        find_system_by_name(name).map_err(|e| LineError::at(line, raw, e.to_string()))
+    };
+
+    let from = system("from")?;
+    let to = system("to")?;
+
+    let kind = get("kind")
+        .map(|(raw, kind)| parse_kind(kind).map_err(|e| LineError::at(line, raw, e)))
+        .transpose()?;
+
+    let one_way = match get("one_way") {
+        None => false,
+        Some((_, "true" | "yes" | "1")) => true,
+        Some((_, "false" | "no" | "0")) => false,
+        Some((raw, other)) => return Err(LineError::at(line, raw, format!("expected true or false in the 'one_way' column, got '{other}'"))),
+    };
+
+    let cost = get("cost")
+        .map(|(raw, cost)| {
+            let cost = cost.parse::<Cost>().map_err(|_| LineError::at(line, raw, format!("invalid cost '{cost}'")))?;
+
+            check_cost(cost).map_err(|e| LineError::at(line, raw, e))
+        })
+        .transpose()?;
+
+    let raw_expires = get("expires").map(|(raw, _)| raw);
+
+    let expires = get("expires")
+        .map(|(raw, expires)| parse_expiry(expires).map_err(|e| LineError::at(line, raw, e)))
+        .transpose()?;
+
+    let size = get("size")
+        .map(|(raw, size)| parse_size(size).map_err(|e| LineError::at(line, raw, e)))
+        .transpose()?;
+
+    let mass = get("mass")
+        .map(|(raw, mass)| parse_mass(mass).map_err(|e| LineError::at(line, raw, e)))
+        .transpose()?;
+
+    let connection = Connection {
+        from,
+        to,
+        kind: kind.unwrap_or(Jump::Custom),
+        one_way,
+        cost,
+        expires,
+        note: get("note").map(|(_, note)| note.to_owned()),
+        size,
+        mass,
+    };
+
+    Ok((connection, raw_expires))
+}
+
+fn parse_csv(file: &str, content: &str, mode: ParseMode, now: SystemTime) -> Result<(Vec<Connection>, Vec<Diagnostic>), ParseErrors> {
+    let mut header = None;
+    let mut bad_header = false;
+    let mut expired = Vec::new();
+
+    let (connections, mut warnings) = diagnostics::parse_lines(file, content, mode, |line| {
+        let trimmed = line.trim();
+
+        // without a header the rows can't be read, and reading the next line as one would only add confusing errors
+        if bad_header || trimmed.is_empty() || trimmed.starts_with('#') {
+            return Ok(None);
+        }
+
+        match &header {
+            None => {
+                let parsed = parse_csv_header(line);
+
+                bad_header = parsed.is_err();
+                header = Some(parsed?);
+
+                Ok(None)
+            },
+            Some(header) => {
+                let (connection, raw_expires) = parse_csv_row(line, header)?;
+
+                match connection.expires {
+                    Some(expires) if expires <= now => {
+                        expired.push(Diagnostic::at_part(file, content, raw_expires.unwrap_or(line), describe_expired(expires)));
+
+                        Ok(None)
+                    },
+                    _ => Ok(Some(connection)),
+                }
+            },
+        }
+    })?;
+
+    warnings.extend(expired);
+
+    Ok((connections, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str, format: ConnectionFormat) -> Result<(Vec<Connection>, Vec<Diagnostic>), ParseErrors> {
+        let now = humantime::parse_rfc3339("2024-05-01T12:00:00Z").unwrap();
+
+        parse_connections("connections", content, format, ParseMode::Strict, now)
+    }
+
+    #[test]
+    fn splits_quoted_csv_fields() {
+        let fields = split_csv_line(r#"Jita, "a ""quoted"", note" ,x"#).unwrap();
+
+        assert_eq!(fields.iter().map(|(_, v)| v.as_str()).collect_vec(), vec!["Jita", "a \"quoted\", note", "x"]);
+        assert_eq!(fields[1].0, r#""a ""quoted"", note""#);
+
+        assert_eq!(split_csv_line(r#"Jita,"open"#).unwrap_err().column, 6);
+    }
+
+    #[test]
+    fn both_formats_give_the_same_connections() {
+        let toml = r#"
+            [[connection]]
+            from = "Jita"
+            to = "Perimeter"
+            kind = "custom"
+            one_way = true
+            cost = 2.5
+            expires = "2024-05-01T18:00:00Z"
+            note = "filament"
+
+            [[connection]]
+            from = "Jita"
+            to = "Tama"
+        "#;
+
+        let csv = "from,to,kind,one_way,cost,expires,note\n\
+                   Jita,Perimeter,custom,true,2.5,2024-05-01T18:00:00Z,filament\n\
+                   # comments and blank lines are ignored\n\
+                   \n\
+                   Jita,Tama,,,,,\n";
+
+        let (connections, _) = parse(toml, ConnectionFormat::Toml).unwrap();
+
+        assert_eq!(parse(csv, ConnectionFormat::Csv).unwrap().0, connections);
+
+        assert_eq!(connections[0].kind, Jump::Custom);
+        assert!(connections[0].one_way);
+        assert_eq!(connections[0].cost, Some(2.5));
+        assert_eq!(connections[0].expires, Some(humantime::parse_rfc3339("2024-05-01T18:00:00Z").unwrap()));
+        assert_eq!(connections[0].note.as_deref(), Some("filament"));
+
+        assert!(!connections[1].one_way);
+        assert_eq!(connections[1].cost, None);
+    }
+
+    #[test]
+    fn expired_connections_are_left_out_with_a_warning() {
+        let csv = "from,to,expires\nJita,Perimeter,2024-05-01T11:00:00Z\nJita,Tama,2024-05-01T13:00:00Z\n";
+
+        let (connections, warnings) = parse(csv, ConnectionFormat::Csv).unwrap();
+
+        assert_eq!(connections.len(), 1);
+        assert_eq!(connections[0].to, find_system_by_name("Tama").unwrap());
+        assert_eq!(warnings.iter().map(|d| (d.line, d.column)).collect_vec(), vec![(2, 16)]);
+        assert_eq!(warnings[0].reason, "expired at 2024-05-01T11:00:00Z, so it's left out");
+
+        let toml = "[[connection]]\nfrom = \"Jita\"\nto = \"Perimeter\"\nexpires = \"2024-05-01T11:00:00Z\"\n";
+
+        let (connections, warnings) = parse(toml, ConnectionFormat::Toml).unwrap();
+
+        assert!(connections.is_empty());
+        assert_eq!(warnings.iter().map(|d| (d.line, d.column)).collect_vec(), vec![(4, 11)]);
+    }
+
+    #[test]
+    fn wormholes_keep_their_size_and_mass() {
+        let csv = "from,to,kind,size,mass\nJita,Thera,wormhole,L,crit\nJita,Tama,wormhole,,\nJita,Perimeter,custom,,\n";
+
+        let (connections, _) = parse(csv, ConnectionFormat::Csv).unwrap();
+
+        let info = connections[0].get_wormhole_info().unwrap();
+
+        assert_eq!((info.size, info.mass), (Some(ShipSize::Large), Some(MassState::Critical)));
+        assert_eq!(connections[1].get_wormhole_info(), None);
+        assert_eq!(connections[2].get_wormhole_info(), None);
+
+        let errors = parse("[[connection]]\nfrom = \"Jita\"\nto = \"Thera\"\nsize = \"huge\"\n", ConnectionFormat::Toml).unwrap_err();
+
+        assert_eq!((errors.0[0].line, errors.0[0].column), (4, 8));
+    }
+
+    #[test]
+    fn bad_entries_point_at_the_value() {
+        let errors = parse("[[connection]]\nfrom = \"Jita\"\nto = \"Nowhere123\"\n", ConnectionFormat::Toml).unwrap_err();
+
+        assert_eq!((errors.0[0].line, errors.0[0].column), (3, 6));
+
+        let errors = parse("[[connection]]\nfrom = \"Jita\"\n", ConnectionFormat::Toml).unwrap_err();
+
+        assert!(errors.0[0].reason.contains("missing field `to`"));
+
+        let errors = parse("from,to,cost\nJita,Tama,-1\nJita,Tama,x\nJita\n", ConnectionFormat::Csv).unwrap_err();
+
+        assert_eq!(errors.0.iter().map(|d| (d.line, d.column)).collect_vec(), vec![(2, 11), (3, 11), (4, 5)]);
+
+        let errors = parse("from,destination\n", ConnectionFormat::Csv).unwrap_err();
+
+        assert_eq!(errors.0[0].column, 6);
+    }
+
+    #[test]
+    fn rows_after_a_bad_header_are_skipped() {
+        let csv = "from,destination\nJita,Perimeter\nfrom,to\nJita,Tama\n";
+
+        let errors = parse(csv, ConnectionFormat::Csv).unwrap_err();
+
+        assert_eq!(errors.0.iter().map(|d| d.line).collect_vec(), vec![1]);
+
+        let now = humantime::parse_rfc3339("2024-05-01T12:00:00Z").unwrap();
+        let (connections, warnings) = parse_connections("connections", csv, ConnectionFormat::Csv, ParseMode::Lenient, now).unwrap();
+
+        assert!(connections.is_empty());
+        assert_eq!(warnings.iter().map(|d| d.line).collect_vec(), vec![1]);
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt::{self, Display};
+
+/// What to do with lines that can't be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// Fail, listing every bad line
+    #[default]
+    Strict,
+    /// Skip bad lines, returning them as warnings
+    Lenient,
+}
+
+/// A problem with one line of an input file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub file: String,
+    /// 1-based
+    pub line: usize,
+    /// 1-based, in characters
+    pub column: usize,
+    /// The whole offending line
+    pub text: String,
+    pub reason: String,
+}
+
+impl Diagnostic {
+    /// A diagnostic pointing at a byte offset into `content`, for formats that aren't parsed line by line.
+    pub fn at_offset(file: &str, content: &str, offset: usize, reason: impl Into<String>) -> Self {
+        let offset = offset.min(content.len());
+
+        let line_start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = content[offset..].find('\n').map_or(content.len(), |i| offset + i);
+
+        Self {
+            file: file.to_owned(),
+            line: content[..offset].matches('\n').count() + 1,
+            column: content[line_start..offset].chars().count() + 1,
+            text: content[line_start..line_end].trim_end_matches('\r').to_owned(),
+            reason: reason.into(),
+        }
+    }
+
+    /// A diagnostic pointing at `part`, which must be a slice of `content`.
+    pub fn at_part(file: &str, content: &str, part: &str, reason: impl Into<String>) -> Self {
+        Self::at_offset(file, content, (part.as_ptr() as usize).saturating_sub(content.as_ptr() as usize), reason)
+    }
+
+    /// A diagnostic pointing at where serde_json gave up on `content`.
+    pub fn at_json_error(file: &str, content: &str, err: &serde_json::Error) -> Self {
+        Self::at_json_error_in(file, content, content, err)
+    }
+
+    /// Like `at_json_error`, for an error from parsing `part`, which must be a slice of `content`.
+    pub fn at_json_error_in(file: &str, content: &str, part: &str, err: &serde_json::Error) -> Self {
+        let start = (part.as_ptr() as usize).saturating_sub(content.as_ptr() as usize);
+
+        // serde_json counts columns in bytes, from 1
+        let line_start = part.split_inclusive('\n').take(err.line().saturating_sub(1)).map(str::len).sum::<usize>();
+
+        Self::at_offset(file, content, start + line_start + err.column().saturating_sub(1), err.to_string())
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // keep tabs, so that the caret lines up with the text however wide the terminal draws them
+        let padding = self.text.chars()
+            .chain(std::iter::repeat(' '))
+            .take(self.column.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+
+        writeln!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.reason)?;
+        writeln!(f, "  | {}", self.text)?;
+        write!(f, "  | {padding}^")
+    }
+}
+
+/// Every bad line in a file, when parsing strictly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseErrors(pub Vec<Diagnostic>);
+
+impl Display for ParseErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bad line{}", self.0.len(), if self.0.len() == 1 { "" } else { "s" })?;
+
+        for diag in &self.0 {
+            write!(f, "\n\n{diag}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseErrors {}
+
+/// Why a line couldn't be parsed, before it's tied to a file and line number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineError {
+    /// 1-based, in characters
+    pub column: usize,
+    pub reason: String,
+}
+
+impl LineError {
+    /// An error pointing at `part`, which must be a slice of `line`.
+    pub fn at(line: &str, part: &str, reason: impl Into<String>) -> Self {
+        let offset = (part.as_ptr() as usize).saturating_sub(line.as_ptr() as usize).min(line.len());
+
+        Self {
+            column: line[..offset].chars().count() + 1,
+            reason: reason.into(),
+        }
+    }
+
+    /// An error pointing just past the end of `line`, for things that are missing.
+    pub fn at_end(line: &str, reason: impl Into<String>) -> Self {
+        Self::at(line, &line[line.len()..], reason)
+    }
+}
+
+/// Parses each line of `content` with `parse`, which returns None for lines that should be ignored.
+///
+/// In strict mode any bad line fails the whole file, and every bad line is listed in the `ParseErrors`. In lenient mode
+/// bad lines are skipped and returned as warnings instead.
+pub fn parse_lines<T>(
+    file: &str,
+    content: &str,
+    mode: ParseMode,
+    mut parse: impl FnMut(&str) -> Result<Option<T>, LineError>,
+) -> Result<(Vec<T>, Vec<Diagnostic>), ParseErrors> {
+    let mut parsed = Vec::new();
+    let mut diagnostics = Vec::new();
+
+    for (idx, line) in content.lines().enumerate() {
+        match parse(line) {
+            Ok(Some(value)) => parsed.push(value),
+            Ok(None) => {},
+            Err(err) => diagnostics.push(Diagnostic {
+                file: file.to_owned(),
+                line: idx + 1,
+                column: err.column,
+                text: line.to_owned(),
+                reason: err.reason,
+            }),
+        }
+    }
+
+    finish(parsed, diagnostics, mode)
+}
+
+/// Fails with every diagnostic in strict mode, or returns them as warnings in lenient mode.
+pub fn finish<T>(parsed: Vec<T>, diagnostics: Vec<Diagnostic>, mode: ParseMode) -> Result<(Vec<T>, Vec<Diagnostic>), ParseErrors> {
+    match mode {
+        ParseMode::Strict if !diagnostics.is_empty() => Err(ParseErrors(diagnostics)),
+        _ => Ok((parsed, diagnostics)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_number(line: &str) -> Result<Option<u32>, LineError> {
+        let line_trimmed = line.trim();
+
+        if line_trimmed.is_empty() {
+            return Ok(None);
+        }
+
+        line_trimmed.parse().map(Some).map_err(|_| LineError::at(line, line_trimmed, "not a number"))
+    }
+
+    #[test]
+    fn strict_mode_lists_every_bad_line() {
+        let errors = parse_lines("numbers.txt", "1\n  x\n\n3\ny", ParseMode::Strict, parse_number).unwrap_err();
+
+        assert_eq!(errors.0.iter().map(|d| (d.line, d.column)).collect::<Vec<_>>(), vec![(2, 3), (5, 1)]);
+        assert_eq!(errors.0[0].text, "  x");
+        assert!(errors.to_string().starts_with("2 bad lines"));
+    }
+
+    #[test]
+    fn lenient_mode_skips_bad_lines() {
+        let (parsed, warnings) = parse_lines("numbers.txt", "1\n  x\n\n3", ParseMode::Lenient, parse_number).unwrap();
+
+        assert_eq!(parsed, vec![1, 3]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].to_string(), "numbers.txt:2:3: not a number\n  |   x\n  |   ^");
+    }
+
+    #[test]
+    fn offsets_become_lines_and_columns() {
+        let content = "a = 1\r\nbb = oops\n";
+
+        let diag = Diagnostic::at_offset("file.toml", content, content.find("oops").unwrap(), "bad value");
+
+        assert_eq!((diag.line, diag.column), (2, 6));
+        assert_eq!(diag.text, "bb = oops");
+    }
+}
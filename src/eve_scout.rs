@@ -0,0 +1,201 @@
+//! Thera and Turnur connections from Eve-Scout's signature list, saved as JSON.
+//!
+//! Each entry is one wormhole, with a signature at both ends:
+//!
+//! ```json
+//! [{
+//!   "signature_type": "wormhole",
+//!   "wh_type": "Q063",
+//!   "max_ship_size": "medium",
+//!   "expires_at": "2024-05-01T18:00:00.000Z",
+//!   "remaining_hours": 6,
+//!   "out_system_id": 31000005,
+//!   "out_system_name": "Thera",
+//!   "out_signature": "ABC-123",
+//!   "in_system_id": 30000142,
+//!   "in_system_name": "Jita",
+//!   "in_signature": "XYZ-789"
+//! }]
+//! ```
+//!
+//! Anything else in an entry is ignored, as are entries that aren't wormholes.
+
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
+use crate::{
+    diagnostics::{self, Diagnostic, ParseErrors, ParseMode},
+    systems::{self, SystemId},
+    wormhole::{ShipSize, WormholeInfo},
+};
+
+/// One wormhole from the signature list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoutedWormhole {
+    /// Thera or Turnur
+    pub out_system: SystemId,
+    /// The system on the other side
+    pub in_system: SystemId,
+    /// What is known about the jump from `out_system` to `in_system`, with the signature in `out_system`
+    pub outward: WormholeInfo,
+    /// What is known about the jump back, with the signature in `in_system`
+    pub inward: WormholeInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    signature_type: Option<String>,
+    wh_type: Option<String>,
+    max_ship_size: Option<String>,
+    expires_at: Option<String>,
+    remaining_hours: Option<f64>,
+    out_system_id: SystemId,
+    out_system_name: Option<String>,
+    out_signature: Option<String>,
+    in_system_id: SystemId,
+    in_system_name: Option<String>,
+    in_signature: Option<String>,
+}
+
+fn parse_size(size: &str) -> Result<ShipSize, String> {
+    match size.to_lowercase().as_str() {
+        "xlarge" | "capital" => Ok(ShipSize::Xl),
+        other => ShipSize::from_tag(other).ok_or_else(|| format!("unknown max_ship_size '{size}'")),
+    }
+}
+
+fn get_system(id: SystemId, name: Option<&str>) -> Result<SystemId, String> {
+    match systems::get_system_index(id) {
+        Some(_) => Ok(id),
+        None => Err(format!("could not find system {id} ({})", name.unwrap_or("no name"))),
+    }
+}
+
+fn parse_entry(entry: Entry, now: SystemTime) -> Result<Option<ScoutedWormhole>, String> {
+    if entry.signature_type.as_deref().is_some_and(|t| t != "wormhole") {
+        return Ok(None);
+    }
+
+    let out_system = get_system(entry.out_system_id, entry.out_system_name.as_deref())?;
+    let in_system = get_system(entry.in_system_id, entry.in_system_name.as_deref())?;
+
+    let size = entry.max_ship_size.as_deref().map(parse_size).transpose()?;
+
+    let expires = match (&entry.expires_at, entry.remaining_hours) {
+        (Some(expires), _) => Some(humantime::parse_rfc3339_weak(expires).map_err(|e| format!("invalid expires_at '{expires}' ({e})"))?),
+        (None, Some(hours)) if hours.is_finite() && hours >= 0.0 => Some(now + Duration::from_secs_f64(hours * 3600.0)),
+        (None, Some(hours)) => return Err(format!("invalid remaining_hours {hours}")),
+        (None, None) => None,
+    };
+
+    if expires.is_some_and(|expires| expires <= now) {
+        return Ok(None);
+    }
+
+    let info = WormholeInfo {
+        size,
+        expires,
+        wh_type: entry.wh_type.filter(|t| !t.is_empty()),
+        ..Default::default()
+    };
+
+    Ok(Some(ScoutedWormhole {
+        out_system,
+        in_system,
+        outward: WormholeInfo { signature: entry.out_signature.filter(|s| !s.is_empty()), ..info.clone() },
+        inward: WormholeInfo { signature: entry.in_signature.filter(|s| !s.is_empty()), ..info },
+    }))
+}
+
+/// Parses a saved signature list. Wormholes that expired before `now` are left out.
+///
+/// `file` is only used to label diagnostics. Returns the entries that were skipped in lenient mode.
+pub fn parse_signatures(file: &str, content: &str, mode: ParseMode, now: SystemTime) -> Result<(Vec<ScoutedWormhole>, Vec<Diagnostic>), ParseErrors> {
+    let entries = serde_json::from_str::<Vec<&RawValue>>(content)
+        .map_err(|e| ParseErrors(vec![Diagnostic::at_json_error(file, content, &e)]))?;
+
+    let mut wormholes = Vec::new();
+    let mut diagnostics = Vec::new();
+
+    for raw in entries {
+        // every entry borrows from `content`, so errors can point at the entry
+        let offset = raw.get().as_ptr() as usize - content.as_ptr() as usize;
+
+        let parsed = serde_json::from_str::<Entry>(raw.get())
+            .map_err(|e| e.to_string())
+            .and_then(|entry| parse_entry(entry, now));
+
+        match parsed {
+            Ok(wormhole) => wormholes.extend(wormhole),
+            Err(reason) => diagnostics.push(Diagnostic::at_offset(file, content, offset, reason)),
+        }
+    }
+
+    diagnostics::finish(wormholes, diagnostics, mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIGNATURES: &str = r#"[
+        {
+            "id": "1",
+            "signature_type": "wormhole",
+            "wh_type": "Q063",
+            "max_ship_size": "xlarge",
+            "expires_at": "2024-05-01T18:00:00.000Z",
+            "remaining_hours": 6,
+            "out_system_id": 31000005,
+            "out_system_name": "Thera",
+            "out_signature": "ABC-123",
+            "in_system_id": 30000142,
+            "in_system_name": "Jita",
+            "in_signature": "XYZ-789"
+        },
+        {
+            "id": "2",
+            "signature_type": "wormhole",
+            "expires_at": "2024-05-01T11:00:00.000Z",
+            "out_system_id": 31000005,
+            "in_system_id": 30000144
+        },
+        { "id": "3", "signature_type": "combat", "out_system_id": 31000005, "in_system_id": 30000142 }
+    ]"#;
+
+    fn now() -> SystemTime {
+        humantime::parse_rfc3339("2024-05-01T12:00:00Z").unwrap()
+    }
+
+    #[test]
+    fn keeps_the_signature_at_each_end() {
+        let (wormholes, _) = parse_signatures("signatures.json", SIGNATURES, ParseMode::Strict, now()).unwrap();
+
+        assert_eq!(wormholes.len(), 1);
+
+        let hole = &wormholes[0];
+
+        assert_eq!((hole.out_system, hole.in_system), (31000005, 30000142));
+        assert_eq!(hole.outward.signature.as_deref(), Some("ABC-123"));
+        assert_eq!(hole.inward.signature.as_deref(), Some("XYZ-789"));
+        assert_eq!(hole.inward.wh_type.as_deref(), Some("Q063"));
+        assert_eq!(hole.inward.size, Some(ShipSize::Xl));
+        assert_eq!(hole.inward.get_remaining_life(now()), Some(Duration::from_secs(6 * 3600)));
+    }
+
+    #[test]
+    fn bad_entries_point_at_the_entry() {
+        let content = "[\n  {\"out_system_id\": 31000005, \"in_system_id\": 1}\n]";
+
+        let errors = parse_signatures("signatures.json", content, ParseMode::Strict, now()).unwrap_err();
+
+        assert_eq!((errors.0[0].line, errors.0[0].column), (2, 3));
+        assert_eq!(errors.0[0].reason, "could not find system 1 (no name)");
+
+        let errors = parse_signatures("signatures.json", "[\n  {,\n]", ParseMode::Strict, now()).unwrap_err();
+
+        assert_eq!((errors.0[0].line, errors.0[0].column), (2, 4));
+    }
+}
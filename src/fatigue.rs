@@ -0,0 +1,299 @@
+use std::{collections::{BinaryHeap, HashMap}, cmp::Ordering, hash::Hash};
+
+use crate::search::{Cost, Hop};
+
+/// Jump fatigue never goes above 5 hours.
+pub const MAX_FATIGUE: f64 = 300.0;
+
+/// The jump activation cooldown never goes above 30 minutes.
+pub const MAX_COOLDOWN: f64 = 30.0;
+
+/// A pilot's jump timers. All times are in minutes since the start of the route.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JumpTimers {
+    /// When the pilot arrived in their current system
+    pub time: f64,
+    /// When the jump drive can be activated again
+    pub ready_at: f64,
+    /// When the jump fatigue wears off, since it decays one minute per minute
+    pub fatigue_until: f64,
+}
+
+impl JumpTimers {
+    pub fn new(fatigue: f64) -> Self {
+        Self {
+            time: 0.0,
+            ready_at: 0.0,
+            fatigue_until: fatigue.min(MAX_FATIGUE),
+        }
+    }
+
+    pub fn get_fatigue(&self) -> f64 {
+        (self.fatigue_until - self.time).max(0.0)
+    }
+
+    pub fn get_cooldown(&self) -> f64 {
+        (self.ready_at - self.time).max(0.0)
+    }
+
+    /// Jumps `ly` light years as soon as the jump drive is ready.
+    ///
+    /// `reduction` is the ship's reduction to the effective distance, which both timers are based on.
+    pub fn jump(&self, ly: f64, reduction: f64) -> Self {
+        let time = self.ready_at.max(self.time);
+        let fatigue = (self.fatigue_until - time).max(0.0);
+
+        let effective = ly * (1.0 - reduction);
+
+        let cooldown = (fatigue / 10.0).max(1.0 + effective).min(MAX_COOLDOWN);
+        let fatigue = (fatigue.max(10.0) * (1.0 + effective)).min(MAX_FATIGUE);
+
+        Self {
+            time,
+            ready_at: time + cooldown,
+            fatigue_until: time + fatigue,
+        }
+    }
+
+    /// Whether every future jump from these timers is at least as soon, with at most as much fatigue, as from `other`.
+    fn dominates(&self, other: &Self) -> bool {
+        self.ready_at <= other.ready_at && self.fatigue_until <= other.fatigue_until.max(other.ready_at)
+    }
+}
+
+/// The total cost, the hops, and the timers on arrival.
+pub type TimedPath<N, E> = (Cost, Vec<Hop<N, E>>, JumpTimers);
+
+/// Formats a duration in minutes as hours and minutes, or minutes and seconds.
+pub fn format_duration(minutes: f64) -> String {
+    let seconds = (minutes * 60.0).round() as u64;
+
+    if seconds >= 3600 {
+        format!("{}h {:02}m", seconds / 3600, (seconds % 3600) / 60)
+    } else {
+        format!("{}m {:02}s", seconds / 60, seconds % 60)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Label<N, E> {
+    node: N,
+    parent: Option<(usize, E)>,
+    /// The cost of the edges taken so far, not counting time
+    cost: Cost,
+    timers: JumpTimers,
+}
+
+impl<N, E> Label<N, E> {
+    /// The total cost if the route were to end here: the edge costs plus every minute spent waiting.
+    ///
+    /// The two are added as they are, so an edge cost of 1 is worth a minute on the timers. Callers that want a
+    /// different exchange rate scale their edge costs.
+    fn get_total(&self) -> Cost {
+        self.cost + self.timers.time
+    }
+}
+
+/// Whether a label settled at `label`'s node makes it pointless: it cost no more, and it's ready as soon with as little
+/// fatigue. Everything settled has a total no higher than any label found later, so time doesn't need comparing.
+fn is_dominated<N: Eq + Hash, E>(labels: &[Label<N, E>], settled: &HashMap<N, Vec<usize>>, label: &Label<N, E>) -> bool {
+    settled.get(&label.node).is_some_and(|at_node| {
+        at_node.iter().any(|other| labels[*other].cost <= label.cost && labels[*other].timers.dominates(&label.timers))
+    })
+}
+
+struct State {
+    total: Cost,
+    label: usize,
+}
+
+impl PartialEq for State {
+    fn eq(&self, other: &Self) -> bool {
+        self.total.total_cmp(&other.total) == Ordering::Equal
+    }
+}
+
+impl Eq for State {}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.total.total_cmp(&self.total)
+    }
+}
+
+/// Finds the path from `from` to `to` with the lowest edge cost plus minutes spent waiting on jump timers, where an
+/// edge cost of 1 is worth a minute.
+///
+/// `edges` returns the outgoing edges of a node as `(neighbour, via, cost, light years)`, where the distance is only
+/// set for jump drive hops. Every other hop is instant and doesn't touch the timers. Jumps are always made as soon as
+/// the drive is ready: waiting longer only lowers the next cooldown by a tenth of the wait, so it never pays off.
+///
+/// Since the cost of a hop depends on the fatigue built up before it, each system can hold several labels, and a
+/// label is only discarded when an earlier one at the same system is ready sooner with less fatigue.
+pub fn fastest_path<N, E, I>(
+    from: N,
+    to: N,
+    start: JumpTimers,
+    reduction: f64,
+    edges: impl FnMut(N) -> I,
+) -> Option<TimedPath<N, E>>
+where
+    N: Copy + Eq + Hash,
+    E: Copy,
+    I: IntoIterator<Item = (N, E, Cost, Option<f64>)>,
+{
+    fastest_path_to_any(from, |node| node == to, start, reduction, edges)
+}
+
+/// Like `fastest_path`, but to whichever node `is_target` accepts first.
+pub fn fastest_path_to_any<N, E, I>(
+    from: N,
+    mut is_target: impl FnMut(N) -> bool,
+    start: JumpTimers,
+    reduction: f64,
+    mut edges: impl FnMut(N) -> I,
+) -> Option<TimedPath<N, E>>
+where
+    N: Copy + Eq + Hash,
+    E: Copy,
+    I: IntoIterator<Item = (N, E, Cost, Option<f64>)>,
+{
+    let mut labels = vec![Label { node: from, parent: None, cost: 0.0, timers: start }];
+    let mut settled = HashMap::<N, Vec<usize>>::new();
+
+    let mut heap = BinaryHeap::new();
+
+    heap.push(State { total: start.time, label: 0 });
+
+    while let Some(State { label: idx, .. }) = heap.pop() {
+        let label = labels[idx];
+
+        // labels are also checked when they're pushed, but something settled since then can still beat this one
+        if is_dominated(&labels, &settled, &label) {
+            continue;
+        }
+
+        settled.entry(label.node).or_default().push(idx);
+
+        if is_target(label.node) {
+            let mut path = Vec::new();
+            let mut curr = idx;
+
+            while let Some((parent, via)) = labels[curr].parent {
+                path.push((labels[curr].node, labels[parent].node, via));
+                curr = parent;
+            }
+
+            path.reverse();
+
+            return Some((label.get_total() - start.time, path, label.timers));
+        }
+
+        for (neighbour, via, cost, ly) in edges(label.node) {
+            let next = Label {
+                node: neighbour,
+                parent: Some((idx, via)),
+                cost: label.cost + cost,
+                timers: match ly {
+                    Some(ly) => label.timers.jump(ly, reduction),
+                    None => label.timers,
+                },
+            };
+
+            if is_dominated(&labels, &settled, &next) {
+                continue;
+            }
+
+            heap.push(State { total: next.get_total(), label: labels.len() });
+            labels.push(next);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timers_follow_the_game_formulas() {
+        // 5 LY with no fatigue: 6 minute cooldown, 10 * (1 + 5) = 60 minutes of fatigue
+        let first = JumpTimers::new(0.0).jump(5.0, 0.0);
+
+        assert_eq!(first.get_cooldown(), 6.0);
+        assert_eq!(first.get_fatigue(), 60.0);
+
+        // the next jump waits out the cooldown, so it starts with 54 minutes of fatigue
+        let second = first.jump(2.0, 0.0);
+
+        assert_eq!(second.time, 6.0);
+        assert_eq!(second.get_cooldown(), 5.4);
+        assert_eq!(second.get_fatigue(), 54.0 * 3.0);
+    }
+
+    #[test]
+    fn fatigue_reductions_and_caps_apply() {
+        // a jump freighter's 10 LY jump only counts as 1 LY
+        let jf = JumpTimers::new(0.0).jump(10.0, 0.9);
+
+        assert!((jf.get_cooldown() - 2.0).abs() < 1e-9);
+        assert!((jf.get_fatigue() - 20.0).abs() < 1e-9);
+
+        let capped = JumpTimers::new(290.0).jump(7.0, 0.0);
+
+        assert_eq!(capped.get_fatigue(), MAX_FATIGUE);
+        assert_eq!(capped.get_cooldown(), 29.0);
+    }
+
+    #[test]
+    fn prefers_short_hops_when_they_arrive_sooner() {
+        // two 7 LY jumps arrive after 8 minutes, three 2 LY jumps after 6 minutes
+        let edges = |n: u32| -> Vec<(u32, (), Cost, Option<f64>)> {
+            match n {
+                1 => vec![(2, (), 1.0, Some(7.0)), (10, (), 1.0, Some(2.0))],
+                2 => vec![(3, (), 1.0, Some(7.0))],
+                10 => vec![(11, (), 1.0, Some(2.0))],
+                11 => vec![(3, (), 1.0, Some(2.0))],
+                _ => vec![],
+            }
+        };
+
+        let (cost, path, timers) = fastest_path(1, 3, JumpTimers::new(0.0), 0.0, edges).unwrap();
+
+        assert_eq!(path.iter().map(|(to, _, _)| *to).collect::<Vec<_>>(), vec![10, 11, 3]);
+        assert_eq!(timers.time, 6.0);
+        assert_eq!(cost, 9.0);
+    }
+
+    #[test]
+    fn keeps_slower_labels_that_have_less_fatigue() {
+        // jumping straight to 2 is cheap, but taking the gates there first leaves the drive ready for the next two jumps
+        let edges = |n: u32| -> Vec<(u32, (), Cost, Option<f64>)> {
+            match n {
+                1 => vec![(2, (), 1.0, Some(7.0)), (5, (), 1.0, None)],
+                5 => vec![(6, (), 1.0, None)],
+                6 => vec![(2, (), 1.0, None)],
+                2 => vec![(3, (), 1.0, Some(7.0))],
+                3 => vec![(4, (), 1.0, Some(7.0))],
+                _ => vec![],
+            }
+        };
+
+        let (_, path, _) = fastest_path(1, 4, JumpTimers::new(0.0), 0.0, edges).unwrap();
+
+        assert_eq!(path.iter().map(|(to, _, _)| *to).collect::<Vec<_>>(), vec![5, 6, 2, 3, 4]);
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(6.5), "6m 30s");
+        assert_eq!(format_duration(125.0), "2h 05m");
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::{anyhow, bail};
+
+use crate::{
+    find_region_by_name, find_system_by_name,
+    systems::{System, SystemId},
+    wormhole::{WormholeFilter, WormholeInfo},
+    SystemSecurity,
+};
+
+/// Which systems routes should stay out of, and which wormholes they can't take.
+///
+/// Jumps into a filtered system from an unfiltered one are removed when `strict` is set. Otherwise (and once a route
+/// is already in a filtered system) they're only penalized, so routes try to get out as soon as possible. Wormholes
+/// that `wormholes` rules out are always removed.
+#[derive(Debug, Clone)]
+pub struct SystemValidityChecker {
+    pub invalid_securities: Vec<SystemSecurity>,
+    pub invalid_regions: Vec<i64>,
+    /// When not empty, only these nullsec regions are valid
+    pub valid_ns_regions: Vec<i64>,
+    pub invalid_systems: Vec<i64>,
+    pub strict: bool,
+    pub wormholes: WormholeFilter,
+}
+
+impl Default for SystemValidityChecker {
+    fn default() -> Self {
+        Self {
+            invalid_securities: Vec::new(),
+            invalid_regions: Vec::new(),
+            valid_ns_regions: Vec::new(),
+            invalid_systems: Vec::new(),
+            strict: true,
+            wormholes: WormholeFilter::default(),
+        }
+    }
+}
+
+impl SystemValidityChecker {
+    pub fn is_system_valid(&self, sys: &System) -> bool {
+        if self.invalid_securities.contains(&sys.get_security_class()) {
+            return false;
+        }
+
+        if self.invalid_systems.contains(&sys.id) {
+            return false;
+        }
+
+        if self.invalid_regions.contains(&sys.region_id) {
+            return false;
+        }
+
+        if sys.get_security_class() == SystemSecurity::Nullsec && !self.valid_ns_regions.is_empty() && !self.valid_ns_regions.contains(&sys.region_id) {
+            return false;
+        }
+
+        true
+    }
+
+    /// Whether a route may jump from `from` to `to` at all.
+    pub fn is_jump_allowed(&self, from: &System, to: &System) -> bool {
+        !(self.strict && self.is_system_valid(from) && !self.is_system_valid(to))
+    }
+
+    pub fn is_wormhole_allowed(&self, info: &WormholeInfo) -> bool {
+        self.wormholes.is_allowed(info)
+    }
+}
+
+/// Something a system can be asked to be, written like `highsec`, `region=The Forge`, or `system=Jita,Amarr`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SystemCriterion {
+    Security(SystemSecurity),
+    Region(i64),
+    /// Any of these systems
+    Systems(Vec<SystemId>),
+}
+
+impl SystemCriterion {
+    pub fn parse(arg: &str) -> anyhow::Result<Self> {
+        let Some((key, value)) = arg.split_once('=') else {
+            return SystemSecurity::from_name(arg.trim())
+                .map(SystemCriterion::Security)
+                .map_err(|_| anyhow!("expected a security (like highsec), region=<name>, or system=<name,name,...>, got '{arg}'"));
+        };
+
+        match key.trim().to_lowercase().as_str() {
+            "security" => Ok(SystemCriterion::Security(SystemSecurity::from_name(value.trim())?)),
+            "region" => Ok(SystemCriterion::Region(find_region_by_name(value.trim())?)),
+            "system" | "systems" => {
+                let systems = value.split(',')
+                    .map(|name| find_system_by_name(name.trim()))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+
+                Ok(SystemCriterion::Systems(systems))
+            },
+            other => bail!("unknown criterion '{other}': expected security, region, or system"),
+        }
+    }
+
+    pub fn matches(&self, sys: &System) -> bool {
+        match self {
+            SystemCriterion::Security(security) => sys.get_security_class() == *security,
+            SystemCriterion::Region(region) => sys.region_id == *region,
+            SystemCriterion::Systems(systems) => systems.contains(&sys.id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::find_system_by_id;
+
+    #[test]
+    fn criteria_parse_and_match() {
+        let jita = find_system_by_id(find_system_by_name("Jita").unwrap());
+        let perimeter = find_system_by_name("Perimeter").unwrap();
+
+        assert_eq!(SystemCriterion::parse("highsec").unwrap(), SystemCriterion::Security(SystemSecurity::Highsec));
+        assert_eq!(SystemCriterion::parse("system=Jita, Perimeter").unwrap(), SystemCriterion::Systems(vec![jita.id, perimeter]));
+
+        assert!(SystemCriterion::parse("region=The Forge").unwrap().matches(jita));
+        assert!(!SystemCriterion::parse("lowsec").unwrap().matches(jita));
+
+        assert!(SystemCriterion::parse("nearby").is_err());
+        assert!(SystemCriterion::parse("planet=Jita IV").is_err());
+        assert!(SystemCriterion::parse("system=Nowhere123").is_err());
+    }
+}
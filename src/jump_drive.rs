@@ -0,0 +1,88 @@
+use clap::ValueEnum;
+
+use crate::{systems::System, SystemSecurity};
+
+pub const METERS_PER_LY: f64 = 9_460_730_472_580_800.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ShipClass {
+    Carrier,
+    Dreadnought,
+    ForceAuxiliary,
+    Supercarrier,
+    Titan,
+    JumpFreighter,
+    Rorqual,
+    BlackOps,
+}
+
+impl ShipClass {
+    /// The jump range in light years with Jump Drive Calibration at level 0.
+    pub fn get_base_range(&self) -> f64 {
+        match self {
+            ShipClass::Carrier | ShipClass::Dreadnought | ShipClass::ForceAuxiliary => 3.5,
+            ShipClass::Supercarrier | ShipClass::Titan => 3.0,
+            ShipClass::JumpFreighter | ShipClass::Rorqual => 5.0,
+            ShipClass::BlackOps => 4.0,
+        }
+    }
+
+    /// How much shorter jumps count as when building up jump fatigue.
+    pub fn get_fatigue_reduction(&self) -> f64 {
+        match self {
+            ShipClass::JumpFreighter | ShipClass::Rorqual => 0.9,
+            ShipClass::BlackOps => 0.75,
+            _ => 0.0,
+        }
+    }
+
+    /// The jump range in light years. Each level of Jump Drive Calibration adds 20% of the base range.
+    pub fn get_range(&self, jdc: u8) -> f64 {
+        self.get_base_range() * (1.0 + 0.2 * jdc as f64)
+    }
+}
+
+pub fn get_distance_ly(from: &System, to: &System) -> f64 {
+    let [x1, y1, z1] = from.position;
+    let [x2, y2, z2] = to.position;
+
+    ((x2 - x1).powi(2) + (y2 - y1).powi(2) + (z2 - z1).powi(2)).sqrt() / METERS_PER_LY
+}
+
+/// Whether a jump drive can be activated in this system. Jump drives don't work in j-space or the special systems.
+pub fn can_jump_from(sys: &System) -> bool {
+    matches!(sys.get_security_class(), SystemSecurity::Highsec | SystemSecurity::Lowsec | SystemSecurity::Nullsec)
+}
+
+/// Whether a jump drive can land in this system, which needs a cyno.
+///
+/// Cynos can't be lit in highsec, j-space, or the special systems. Systems without any stargates (Jove space) can't be
+/// reached to light one either.
+pub fn can_jump_to(sys: &System) -> bool {
+    matches!(sys.get_security_class(), SystemSecurity::Lowsec | SystemSecurity::Nullsec) && !sys.jumps.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranges_match_the_game() {
+        assert_eq!(ShipClass::Carrier.get_range(5), 7.0);
+        assert_eq!(ShipClass::Titan.get_range(5), 6.0);
+        assert_eq!(ShipClass::JumpFreighter.get_range(5), 10.0);
+        assert_eq!(ShipClass::BlackOps.get_range(0), 4.0);
+        assert!((ShipClass::Dreadnought.get_range(4) - 6.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn distance_is_in_light_years() {
+        let mut a = crate::tests::test_system("A", 0.1);
+        let mut b = crate::tests::test_system("B", -0.2);
+
+        a.position = [METERS_PER_LY, 0.0, 0.0];
+        b.position = [METERS_PER_LY * 4.0, METERS_PER_LY * 4.0, 0.0];
+
+        assert!((get_distance_ly(&a, &b) - 5.0).abs() < 1e-9);
+    }
+}
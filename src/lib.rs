@@ -0,0 +1,268 @@
+//! Route planning for Eve Online.
+//!
+//! A [`Universe`] holds every jump that can be taken: stargates (or jump drive hops), plus any wormholes and
+//! ansiblexes loaded on top. A [`RouteRequest`] describes a trip through some waypoints, and planning it against a
+//! universe gives a [`PlannedRoute`].
+//!
+//! ```no_run
+//! use yaerp::{find_system_by_name, RoutePreference, RouteRequest, SystemSecurity, SystemValidityChecker, Universe};
+//!
+//! let universe = Universe::stargates();
+//!
+//! let filter = SystemValidityChecker {
+//!     invalid_securities: vec![SystemSecurity::Nullsec],
+//!     ..Default::default()
+//! };
+//!
+//! let route = RouteRequest::new(vec![find_system_by_name("Jita")?, find_system_by_name("Amarr")?])
+//!     .filter(filter)
+//!     .preference(RoutePreference::Safer)
+//!     .plan(&universe)?;
+//!
+//! println!("{} jumps", route.get_jump_count());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::{collections::HashMap, sync::OnceLock};
+
+use anyhow::bail;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+pub mod sde;
+pub mod sde_cache;
+
+pub mod systems;
+pub use crate::systems::{System, SystemId, SystemIndex, Stargate};
+
+pub mod search;
+pub use crate::search::Cost;
+
+pub mod tour;
+
+pub mod jump_drive;
+pub use crate::jump_drive::ShipClass;
+
+pub mod fatigue;
+pub use crate::fatigue::JumpTimers;
+
+pub mod warp;
+pub use crate::warp::WarpProfile;
+
+pub mod diagnostics;
+pub use crate::diagnostics::{Diagnostic, ParseMode};
+
+pub mod universe;
+pub use crate::universe::{Csr, Universe};
+
+pub mod filter;
+pub use crate::filter::{SystemCriterion, SystemValidityChecker};
+
+pub mod route;
+pub use crate::route::{CostModel, Detour, Leg, NearbySystem, Path, PlannedRoute, RouteMode, RoutePreference, RouteRequest};
+
+pub mod connections;
+pub use crate::connections::{Connection, ConnectionFormat};
+
+pub mod wormhole;
+pub use crate::wormhole::{MassState, ShipSize, WormholeFilter, WormholeInfo};
+
+pub mod eve_scout;
+
+pub mod chain;
+
+pub mod overlays;
+pub use crate::overlays::{OverlayFiles, OverlayUniverse};
+
+pub mod server;
+
+pub mod shell;
+
+pub mod tui;
+
+pub mod output;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Jump {
+    Wormhole,
+    Gate,
+    Ansiblex,
+    Cyno,
+    /// Anything else from a connections file, like a filament
+    Custom,
+}
+
+impl Jump {
+    pub const ALL: [Jump; 5] = [Jump::Wormhole, Jump::Gate, Jump::Ansiblex, Jump::Cyno, Jump::Custom];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Jump::Wormhole => "wormhole",
+            Jump::Gate => "gate",
+            Jump::Ansiblex => "ansiblex",
+            Jump::Cyno => "cyno",
+            Jump::Custom => "custom",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum SystemSecurity {
+    Highsec,
+    Lowsec,
+    Nullsec,
+    Jspace,
+    Special,
+}
+
+impl SystemSecurity {
+    pub const ALL: [SystemSecurity; 5] = [SystemSecurity::Highsec, SystemSecurity::Lowsec, SystemSecurity::Nullsec, SystemSecurity::Jspace, SystemSecurity::Special];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SystemSecurity::Highsec => "highsec",
+            SystemSecurity::Lowsec => "lowsec",
+            SystemSecurity::Nullsec => "nullsec",
+            SystemSecurity::Jspace => "jspace",
+            SystemSecurity::Special => "special",
+        }
+    }
+
+    pub fn from_name(name: &str) -> anyhow::Result<Self> {
+        let name = name.to_lowercase();
+
+        match Self::ALL.into_iter().find(|s| s.name() == name) {
+            Some(security) => Ok(security),
+            None => bail!("unknown security '{name}': expected highsec, lowsec, nullsec, jspace, or special"),
+        }
+    }
+}
+
+impl System {
+    pub fn get_security_class(&self) -> SystemSecurity {
+        if self.security >= 0.5 {
+            return SystemSecurity::Highsec;
+        }
+
+        if self.security > 0.0 && self.security < 0.5 {
+            return SystemSecurity::Lowsec;
+        }
+
+        if self.region_id == 10000070 { // Pochven
+            return SystemSecurity::Special;
+        }
+
+        if /* Thera */ self.id == 31000005 || /* Turnur */ self.id == 30002086 || /* Zarzakh */ self.id == 30100000 {
+            return SystemSecurity::Special;
+        }
+
+        if self.security == -0.99 { // Wormhole space
+            return SystemSecurity::Jspace;
+        }
+
+        if self.security < 0.0 {
+            return SystemSecurity::Nullsec;
+        }
+
+        panic!()
+    }
+}
+
+static SYSTEMS_BY_NAME: OnceLock<HashMap<&'static str, i64>> = OnceLock::new();
+
+fn init_systems_by_name() -> &'static HashMap<&'static str, i64> {
+    SYSTEMS_BY_NAME.get_or_init(|| {
+        let mut map = HashMap::new();
+
+        for sys in systems::get_systems() {
+            map.insert(sys.name, sys.id);
+        }
+
+        map
+    })
+}
+
+/// Finds a system by its exact name, or by a case-insensitive part of its name if that only matches one system.
+pub fn find_system_by_name(name: &str) -> anyhow::Result<SystemId> {
+    let sys = init_systems_by_name();
+
+    if let Some(id) = sys.get(name) {
+        return Ok(*id);
+    }
+
+    let name = name.to_lowercase();
+
+    let matches = systems::get_systems().iter().filter(|s| s.name.to_lowercase().contains(&name)).collect_vec();
+
+    match matches.len() {
+        0 => {
+            bail!("could not find system '{name}'");
+        }
+        1 => {
+            Ok(matches[0].id)
+        }
+        more => {
+            bail!("ambiguous system name '{name}': matched {more} systems");
+        }
+    }
+}
+
+/// Finds a region by its exact name, or by a case-insensitive part of its name if that only matches one region.
+pub fn find_region_by_name(name: &str) -> anyhow::Result<i64> {
+    let regions = systems::get_regions();
+
+    if let Ok(idx) = regions.binary_search_by(|(n, _)| n.cmp(&name)) {
+        return Ok(regions[idx].1);
+    }
+
+    let name = name.to_lowercase();
+
+    let matches = regions.iter().filter(|s| s.0.to_lowercase().contains(&name)).collect_vec();
+
+    match matches.len() {
+        0 => {
+            bail!("could not find region '{name}'");
+        }
+        1 => {
+            Ok(matches[0].1)
+        }
+        more => {
+            bail!("ambiguous region name '{name}': matched {more} regions");
+        }
+    }
+}
+
+/// Panics if the system doesn't exist. Every ID handed out by this crate does.
+pub fn find_system_by_id(id: SystemId) -> &'static System {
+    find_system_by_index(systems::get_system_index(id).unwrap())
+}
+
+/// Panics if the index is out of range. Every index handed out by this crate is in range.
+pub fn find_system_by_index(idx: SystemIndex) -> &'static System {
+    &systems::get_systems()[idx as usize]
+}
+
+pub fn get_system_name(id: SystemId) -> &'static str {
+    find_system_by_id(id).name
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn test_system(name: &'static str, security: f32) -> System {
+        System {
+            id: 0,
+            name,
+            security,
+            constellation_id: 0,
+            constellation: "",
+            region_id: 0,
+            region: "",
+            position: [0.0; 3],
+            jumps: &[],
+            gates: &[],
+        }
+    }
+}
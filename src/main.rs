@@ -1,16 +1,27 @@
 
-use std::{collections::{HashMap, HashSet, VecDeque}, sync::OnceLock, path::PathBuf};
+use std::{path::PathBuf, time::{Duration, SystemTime}};
 
-use anyhow::{Context, bail, anyhow};
-use itertools::Itertools;
-use clap::Parser;
+use anyhow::{bail, Context};
+use clap::{Parser, Subcommand, ValueEnum};
 
-mod systems;
-use crate::systems::*;
+use yaerp::{
+    find_region_by_name, find_system_by_id, find_system_by_name, get_system_name, output::{self, NearbyReport, RouteReport}, route::{find_nearby, parse_jump_cost}, server::RouteServer, shell::Shell, tui::RouteView,
+    Cost, CostModel, Jump, OverlayFiles, OverlayUniverse, RouteMode, RoutePreference, RouteRequest, ParseMode, ShipClass, ShipSize, SystemId,
+    SystemCriterion, SystemSecurity, SystemValidityChecker, Universe, WarpProfile, WormholeFilter,
+};
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
+#[command(override_usage = "yaerp [OPTIONS] <WAYPOINTS> <WAYPOINTS>...\n       yaerp [OPTIONS] <COMMAND>")]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[doc = "An SDE zip to load the universe from, instead of the one built into yaerp.\n"]
+    #[doc = "The parsed SDE is cached, so only the first start with a new SDE is slow."]
+    #[arg(long = "sde", env = "YAERP_SDE")]
+    sde: Option<PathBuf>,
+
     #[doc = "A file containing copy+pasted bookmarks from the locations window in eve.\n"]
     #[doc = "Each line must match this regex: ^[A-Z]{3}-\\d{3} +[\\w\\-]+ +(-&gt; +[\\w\\-]+|\\(\\w+\\))\\t([^\\t]+\\t){2}[^\\t]+"]
     #[arg(short = 'w', long = "wormholes")]
@@ -21,11 +32,94 @@ struct Args {
     #[arg(short = 'a', long = "ansiblexes")]
     ansiblex_files: Vec<PathBuf>,
 
+    #[doc = "A JSON file of Thera and Turnur connections, saved from Eve-Scout's signature list.\n"]
+    #[doc = "The signatures to scan down are shown next to each wormhole in the route."]
+    #[arg(long = "eve-scout")]
+    eve_scout_files: Vec<PathBuf>,
+
+    #[doc = "A wormhole chain exported from Tripwire, Pathfinder, or Wanderer, as JSON.\n"]
+    #[doc = "The mapper is picked from the shape of the file. See the README for what each export needs."]
+    #[arg(long = "chain")]
+    chain_files: Vec<PathBuf>,
+
+    #[doc = "A TOML or CSV file of extra connections, like filaments or jump bridges from other tools.\n"]
+    #[doc = "Files ending in .csv are read as CSV, anything else as TOML. See the README for the fields."]
+    #[arg(long = "connections")]
+    connection_files: Vec<PathBuf>,
+
+    #[doc = "A jump routes may not take, as 'From -> To', like a camped or disabled gate.\n"]
+    #[doc = "Only that direction is removed. Routes that detour around a removed jump say so."]
+    #[arg(long = "remove-jump")]
+    removed_jumps: Vec<String>,
+
+    #[doc = "A file of jumps routes may not take, with one 'From -> To' per line.\n"]
+    #[doc = "Empty lines and lines starting with # are ignored."]
+    #[arg(long = "removed-jumps")]
+    removed_jump_files: Vec<PathBuf>,
+
+    #[doc = "The size of the ship, so that routes skip wormholes it can't fit through.\n"]
+    #[doc = "Wormholes without a size are assumed to fit anything."]
+    #[arg(long = "ship-size", value_enum)]
+    ship_size: Option<ShipSize>,
+
+    #[doc = "Routes will skip wormholes that expire sooner than this, like 30m or 2h.\n"]
+    #[doc = "When set, wormholes tagged as end of life without an expiry time are skipped too."]
+    #[arg(long = "min-wormhole-life", value_parser = humantime::parse_duration)]
+    min_wormhole_life: Option<Duration>,
+
+    #[doc = "Skip lines in the wormhole, ansiblex, Eve-Scout, chain, and connection files that can't be parsed, printing a warning for each.\n"]
+    #[doc = "Without this, any line that can't be parsed fails the whole run, and every bad line is listed."]
+    #[arg(long = "lenient")]
+    lenient: bool,
+
     #[doc = "Filtered jumps are not removed, but the penalties are still applied.\n"]
-    #[doc = "A filtered jump is counted as 1000 jumps in the distance calculation.\n"]
+    #[doc = "A filtered jump costs 1000 more than a normal jump in the distance calculation.\n"]
     #[arg(long = "no-filter")]
     no_filter: bool,
 
+    #[doc = "The cost of one jump of a given type, as type=cost (gate, wormhole, ansiblex, cyno, or custom).\n"]
+    #[doc = "Every jump costs 1 by default. Routes are picked by their total cost instead of their jump count.\n"]
+    #[doc = "Example: --cost wormhole=3 --cost ansiblex=0.5"]
+    #[arg(short = 'c', long = "cost", value_parser = parse_jump_cost)]
+    jump_costs: Vec<(Jump, Cost)>,
+
+    #[doc = "Which routes to prefer, like the autopilot setting in game.\n"]
+    #[doc = "Unlike the --no-* flags, this only penalizes systems and will still enter them if the detour is long enough."]
+    #[arg(short = 'p', long = "prefer", value_enum, default_value_t = RoutePreference::Shorter)]
+    preference: RoutePreference,
+
+    #[doc = "Plan the route for a ship with a jump drive, jumping between cyno-able systems instead of taking stargates.\n"]
+    #[doc = "Every system after the start must be lowsec or nullsec, since cynos can't be lit anywhere else."]
+    #[arg(short = 'j', long = "jump-drive", value_enum)]
+    jump_drive: Option<ShipClass>,
+
+    /// The pilot's Jump Drive Calibration skill level, which increases the jump range by 20% per level
+    #[arg(long = "jdc", default_value_t = 5, value_parser = clap::value_parser!(u8).range(0..=5))]
+    jump_drive_calibration: u8,
+
+    #[doc = "The pilot's jump fatigue at the start of the route, in minutes.\n"]
+    #[doc = "Only used with --jump-drive."]
+    #[arg(long = "fatigue", default_value_t = 0.0)]
+    fatigue: f64,
+
+    #[doc = "The ship's warp speed in AU/s.\n"]
+    #[doc = "When set, routes are picked by their estimated travel time instead of their jump count, using the distances between gates."]
+    #[arg(long = "warp-speed", conflicts_with = "jump_drive")]
+    warp_speed: Option<f64>,
+
+    /// The ship's align time in seconds (only used with --warp-speed)
+    #[arg(long = "align-time", default_value_t = 10.0)]
+    align_time: f64,
+
+    /// Seconds from landing on a gate to aligning in the next system, for the gate activation, session change, and cloak (only used with --warp-speed)
+    #[arg(long = "jump-overhead", default_value_t = 10.0)]
+    jump_overhead: f64,
+
+    #[doc = "How to print the route.\n"]
+    #[doc = "The JSON output has a version field, which changes whenever a field is removed or changes meaning."]
+    #[arg(short = 'f', long = "format", value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
     /// Routes will never enter Thera, Turnur, Zarzakh, or Pochven, and will try to get out as soon as possible
     #[arg(long = "no-special")]
     no_special: bool,
@@ -70,450 +164,341 @@ struct Args {
     waypoints: Vec<String>,
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
-enum Jump {
-    Wormhole,
-    Gate,
-    Ansiblex,
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[doc = "Answer route, distance, and system requests over HTTP, as JSON.\n"]
+    #[doc = "The universe is loaded once, and the wormhole, ansiblex, and other overlay files are loaded again whenever they change.\n"]
+    #[doc = "Route options like --prefer are given with each request instead. See the README for the API."]
+    Serve {
+        /// The address to listen on. Anything other than localhost lets other machines plan routes too.
+        #[arg(long = "listen", default_value = "127.0.0.1:8080")]
+        listen: String,
+    },
+    #[doc = "Plan routes from an interactive prompt, without loading the universe for every route.\n"]
+    #[doc = "Filters set on the command line (like --no-lowsec) are where the shell starts. Type 'help' in the shell for its commands."]
+    Shell,
+    #[doc = "Show a route full-screen, planned again as waypoints are added and filters are toggled.\n"]
+    #[doc = "Filters set on the command line (like --no-lowsec) are where it starts."]
+    Tui {
+        /// The systems to start with. More can be added from the search box.
+        waypoints: Vec<String>,
+    },
+    #[doc = "List every system within some jumps, with its security, region, and the kinds of jump it takes to get there.\n"]
+    #[doc = "Filters (like --no-lowsec) and loaded wormholes and ansiblexes apply to the jumps taken, the same as for routes."]
+    Near(NearOptions),
+    #[doc = "Find the nearest system that matches every --where, and the route to it.\n"]
+    #[doc = "It's found with one search, so a long list of candidates costs no more than one. A start that matches is its own nearest match."]
+    Nearest {
+        /// The system to start from
+        from: String,
+
+        #[doc = "What the system has to be: a security (highsec, lowsec, nullsec, jspace, or special), region=<name>, or system=<name,name,...>.\n"]
+        #[doc = "Can be given more than once, and every one has to match."]
+        #[arg(long = "where", required = true)]
+        criteria: Vec<String>,
+    },
 }
 
-type JumpMap = HashMap<SystemId, HashSet<(SystemId, Jump)>>;
+#[derive(clap::Args, Debug)]
+struct NearOptions {
+    /// The system to search from
+    system: String,
 
-fn load_stargates(jumps: &mut JumpMap) {
-    for sys in systems::SYSTEMS {
-        for j in sys.jumps {
-            jumps.entry(sys.id).or_default().insert((*j, Jump::Gate));
-        }
-    }
-}
-
-static SYSTEMS_BY_NAME: OnceLock<HashMap<&'static str, i64>> = OnceLock::new();
+    /// How many jumps away to look
+    #[arg(long = "jumps", default_value_t = 5)]
+    jumps: usize,
 
-fn init_systems_by_name() -> &'static HashMap<&'static str, i64> {
-    SYSTEMS_BY_NAME.get_or_init(|| {
-        let mut map = HashMap::new();
+    /// The order the systems are listed in
+    #[arg(long = "sort", value_enum, default_value_t = NearbySort::Jumps)]
+    sort: NearbySort,
 
-        for sys in systems::SYSTEMS {
-            map.insert(sys.name, sys.id);
-        }
+    /// Only list systems of this security: highsec, lowsec, nullsec, jspace, or special. Others can still be passed through.
+    #[arg(long = "security", value_parser = SystemSecurity::from_name)]
+    security: Vec<SystemSecurity>,
 
-        map
-    })
+    /// Only list systems in this region. Others can still be passed through.
+    #[arg(long = "in-region")]
+    regions: Vec<String>,
 }
 
-fn find_system_by_name(name: &str) -> anyhow::Result<SystemId> {
-    let sys = init_systems_by_name();
-
-    if let Some(id) = sys.get(name) {
-        return Ok(*id);
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum NearbySort {
+    /// Nearest first
+    Jumps,
+    /// By name
+    Name,
+    /// Highest security first
+    Security,
+    /// By region, then nearest first
+    Region,
+}
 
-    let name = name.to_lowercase();
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Human readable text
+    Text,
+    /// A single JSON object, for scripts and bots
+    Json,
+}
 
-    let matches = SYSTEMS.iter().filter(|s| s.name.to_lowercase().contains(&name)).collect_vec();
+/// Parses every value given for `flag`. These are parsed here rather than by clap, since names can only be looked up
+/// once the SDE is installed.
+fn parse_names<T>(values: &[String], flag: &str, parse: impl Fn(&str) -> anyhow::Result<T>) -> anyhow::Result<Vec<T>> {
+    values.iter()
+        .map(|value| parse(value).with_context(|| format!("invalid {flag} '{value}'")))
+        .collect()
+}
 
-    match matches.len() {
-        0 => {
-            bail!("could not find system '{name}'");
-        }
-        1 => {
-            Ok(matches[0].id)
-        }
-        more => {
-            bail!("ambiguous system name '{name}': matched {more} systems");
-        }
+fn parse_removed_jump(arg: &str) -> anyhow::Result<(SystemId, SystemId)> {
+    match yaerp::universe::try_parse_removed_line(arg) {
+        Ok(Some(jump)) => Ok(jump),
+        Ok(None) => bail!("expected 'From -> To'"),
+        Err(e) => bail!("{}", e.reason),
     }
 }
 
-fn find_region_by_name(name: &str) -> anyhow::Result<SystemId> {
-    if let Ok(idx) = REGIONS.binary_search_by(|(n, _)| n.cmp(&name)) {
-        return Ok(REGIONS[idx].1);
+fn get_overlay_files(args: &Args) -> OverlayFiles {
+    OverlayFiles {
+        wormholes: args.wormhole_bookmarks.clone(),
+        ansiblexes: args.ansiblex_files.clone(),
+        eve_scout: args.eve_scout_files.clone(),
+        chains: args.chain_files.clone(),
+        connections: args.connection_files.clone(),
+        removed_jumps: args.removed_jump_files.clone(),
     }
+}
 
-    let name = name.to_lowercase();
+fn get_filter(args: &Args) -> anyhow::Result<SystemValidityChecker> {
+    let mut invalid_securities = Vec::<SystemSecurity>::new();
 
-    let matches = REGIONS.iter().filter(|s| s.0.to_lowercase().contains(&name)).collect_vec();
+    if args.no_highsec {
+        invalid_securities.push(SystemSecurity::Highsec);
+    }
 
-    match matches.len() {
-        0 => {
-            bail!("could not find region '{name}'");
-        }
-        1 => {
-            Ok(matches[0].1)
-        }
-        more => {
-            bail!("ambiguous region name '{name}': matched {more} regions");
-        }
+    if args.no_lowsec {
+        invalid_securities.push(SystemSecurity::Lowsec);
     }
-}
 
-fn find_system_by_id(id: SystemId) -> &'static System {
-    &SYSTEMS[SYSTEMS.binary_search_by_key(&id, |s| s.id).unwrap()]
-}
+    if args.no_nullsec {
+        invalid_securities.push(SystemSecurity::Nullsec);
+    }
 
-fn get_system_name(id: SystemId) -> &'static str {
-    find_system_by_id(id).name
-}
+    if args.no_jspace {
+        invalid_securities.push(SystemSecurity::Jspace);
+    }
 
-fn try_parse_wh_line(line: &str) -> anyhow::Result<(SystemId, SystemId)> {
-    let cols = line.split('\t').collect::<Vec<_>>();
-    let bm_name = cols[0]
-        .split(' ')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
-        .collect::<Vec<_>>();
-    let bm_system = cols[3];
-
-    let (a, b) = if bm_name.contains(&"-&gt;") {
-        (bm_name[1], bm_name[3])
-    } else {
-        (bm_name[1], bm_system)
-    };
+    if args.no_special {
+        invalid_securities.push(SystemSecurity::Special);
+    }
 
-    Ok((find_system_by_name(a)?, find_system_by_name(b)?))
+    let invalid_regions = args.region_blacklist.iter()
+        .map(|r| find_region_by_name(r))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let invalid_systems = args.system_blacklist.iter()
+        .map(|sys| find_system_by_name(sys))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let valid_ns_regions = args.ns_region_whitelist.iter()
+        .map(|r| find_region_by_name(r))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(SystemValidityChecker {
+        invalid_securities,
+        invalid_regions,
+        valid_ns_regions,
+        invalid_systems,
+        strict: !args.no_filter,
+        wormholes: WormholeFilter {
+            ship_size: args.ship_size,
+            min_life: args.min_wormhole_life.unwrap_or_default(),
+            now: SystemTime::now(),
+        },
+    })
 }
 
-fn load_wormholes(content: &str, jumps: &mut JumpMap) {
-    for line in content.lines() {
-        let (system_id, dst_id) = try_parse_wh_line(line).with_context(|| format!("could not parse line '{line}'")).unwrap();
-
-        jumps.entry(system_id).or_default().insert((dst_id, Jump::Wormhole));
-        jumps.entry(dst_id).or_default().insert((system_id, Jump::Wormhole));
+fn get_mode(args: &Args) -> RouteMode {
+    if let Some(ship) = args.jump_drive {
+        return RouteMode::JumpDrive(ship);
     }
-}
-
-fn try_parse_ansi_line(mut line: &str) -> anyhow::Result<(SystemId, SystemId)> {
-    line = line.trim_start_matches(&['0', '1', '2', '3', '4', '5', '6', '7', '8', '9']);
 
-    let (from, to) = line.split_once("-->").ok_or_else(|| anyhow!("could not find '-->' on line"))?;
-
-    let from = from.trim();
-    let to = to.trim();
+    if let Some(warp_speed) = args.warp_speed {
+        return RouteMode::Warp(WarpProfile {
+            warp_speed,
+            align_time: args.align_time,
+            jump_overhead: args.jump_overhead,
+        });
+    }
 
-    Ok((find_system_by_name(from)?, find_system_by_name(to)?))
+    RouteMode::Jumps
 }
 
-fn load_ansiblexes(content: &str, jumps: &mut JumpMap) {
-    for mut line in content.lines() {
-        line = line.trim();
-
-        if line.starts_with("#") || line.is_empty() {
-            continue;
-        }
-
-        let (system_id, dst_id) = try_parse_ansi_line(line).with_context(|| format!("could not parse line '{line}'")).unwrap();
-
-        jumps.entry(system_id).or_default().insert((dst_id, Jump::Ansiblex));
-        jumps.entry(dst_id).or_default().insert((system_id, Jump::Ansiblex));
-    }
+/// A request with every route option from the command line.
+fn get_request(args: &Args, waypoints: Vec<SystemId>) -> anyhow::Result<RouteRequest> {
+    Ok(RouteRequest::new(waypoints)
+        .filter(get_filter(args)?)
+        .costs(CostModel::new(&args.jump_costs, args.preference))
+        .mode(get_mode(args))
+        .fatigue(args.fatigue)
+        .exact(args.exact_route))
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-enum SystemSecurity {
-    Highsec,
-    Lowsec,
-    Nullsec,
-    Jspace,
-    Special,
-}
+fn print_nearby(args: &Args, options: &NearOptions, universe: &Universe) -> anyhow::Result<()> {
+    let from = find_system_by_name(&options.system)?;
 
-impl System {
-    fn get_security_class(&self) -> SystemSecurity {
-        if self.security >= 0.5 {
-            return SystemSecurity::Highsec;
-        }
+    let regions = options.regions.iter()
+        .map(|r| find_region_by_name(r))
+        .collect::<Result<Vec<_>, _>>()?;
 
-        if self.security > 0.0 && self.security < 0.5 {
-            return SystemSecurity::Lowsec;
-        }
+    let mut nearby = find_nearby(universe, from, options.jumps, &get_filter(args)?)?;
 
-        if self.region_id == 10000070 { // Pochven
-            return SystemSecurity::Special;
-        }
+    nearby.retain(|near| {
+        let sys = find_system_by_id(near.id);
 
-        if /* Thera */ self.id == 31000005 || /* Turnur */ self.id == 30002086 || /* Zarzakh */ self.id == 30100000 {
-            return SystemSecurity::Special;
-        }
+        (options.security.is_empty() || options.security.contains(&sys.get_security_class()))
+            && (regions.is_empty() || regions.contains(&sys.region_id))
+    });
 
-        if self.security == -0.99 { // Wormhole space
-            return SystemSecurity::Jspace;
-        }
+    // the systems come nearest first, then by name, and the sorts are stable
+    match options.sort {
+        NearbySort::Jumps => {},
+        NearbySort::Name => nearby.sort_by_key(|near| get_system_name(near.id)),
+        NearbySort::Security => nearby.sort_by(|l, r| find_system_by_id(r.id).security.total_cmp(&find_system_by_id(l.id).security)),
+        NearbySort::Region => nearby.sort_by_key(|near| find_system_by_id(near.id).region),
+    }
 
-        if self.security < 0.0 {
-            return SystemSecurity::Nullsec;
-        }
+    if args.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&NearbyReport::new(from, options.jumps, &nearby))?);
 
-        panic!()
+        return Ok(());
     }
-}
 
-struct SystemValidityChecker {
-    invalid_securities: Vec<SystemSecurity>,
-    invalid_regions: Vec<i64>,
-    valid_ns_regions: Vec<i64>,
-    invalid_systems: Vec<i64>,
-}
+    let mut text = String::new();
 
-impl SystemValidityChecker {
-    pub fn new(args: &Args) -> anyhow::Result<Self> {
-        let mut invalid_securities = Vec::<SystemSecurity>::new();
+    output::write_nearby_text(&mut text, from, options.jumps, &nearby)?;
 
-        if args.no_highsec {
-            invalid_securities.push(SystemSecurity::Highsec);
-        }
+    print!("{text}");
 
-        if args.no_lowsec {
-            invalid_securities.push(SystemSecurity::Lowsec);
-        }
-
-        if args.no_nullsec {
-            invalid_securities.push(SystemSecurity::Nullsec);
-        }
+    Ok(())
+}
 
-        if args.no_jspace {
-            invalid_securities.push(SystemSecurity::Jspace);
-        }
+fn print_nearest(args: &Args, from: &str, criteria: &[String], universe: &Universe) -> anyhow::Result<()> {
+    let from = find_system_by_name(from)?;
 
-        if args.no_special {
-            invalid_securities.push(SystemSecurity::Special);
-        }
+    let criteria = parse_names(criteria, "--where", SystemCriterion::parse)?;
 
-        let invalid_regions = args.region_blacklist.iter()
-            .map(|r| find_region_by_name(r))
-            .collect::<Result<Vec<_>, _>>()?;
+    let request = get_request(args, vec![from])?;
 
-        let invalid_systems = args.system_blacklist.iter()
-            .map(|sys| find_system_by_name(sys))
-            .collect::<Result<Vec<_>, _>>()?;
+    let Some(planned) = request.plan_to_nearest(universe, |sys| criteria.iter().all(|c| c.matches(sys)))? else {
+        bail!("no matching system can be reached from {}; your restrictions are likely too strict", get_system_name(from));
+    };
 
-        let valid_ns_regions = args.ns_region_whitelist.iter()
-            .map(|r| find_region_by_name(r))
-            .collect::<Result<Vec<_>, _>>()?;
+    if args.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&RouteReport::new(&planned, universe, &request.filter))?);
 
-        Ok(Self {
-            invalid_securities,
-            invalid_regions,
-            valid_ns_regions,
-            invalid_systems,
-        })
+        return Ok(());
     }
 
-    pub fn is_system_valid(&self, sys: &System) -> bool {
-        if self.invalid_securities.contains(&sys.get_security_class()) {
-            return false;
-        }
-
-        if self.invalid_systems.contains(&sys.id) {
-            return false;
-        }
-
-        if self.invalid_regions.contains(&sys.region_id) {
-            return false;
-        }
+    let nearest = find_system_by_id(planned.waypoints[1]);
 
-        if sys.get_security_class() == SystemSecurity::Nullsec && self.valid_ns_regions.len() > 0 && !self.valid_ns_regions.contains(&sys.region_id) {
-            return false;
-        }
+    if planned.get_jump_count() == 0 {
+        println!("Nearest match: {} ({:.2}, {}), where you already are", nearest.name, nearest.security, nearest.region);
 
-        true
+        return Ok(());
     }
-}
 
-fn filter_jumps(jumps: &mut JumpMap, checker: &SystemValidityChecker) -> anyhow::Result<()> {
-    for (from, to) in jumps.iter_mut() {
-        let from_valid = checker.is_system_valid(find_system_by_id(*from));
+    println!("Nearest match: {} ({:.2}, {}), {} jumps away", nearest.name, nearest.security, nearest.region, planned.get_jump_count());
 
-        to.retain(|(dst, _)| {
-            let to_valid = checker.is_system_valid(find_system_by_id(*dst));
-            
-            let invalid_jump = from_valid && !to_valid;
+    let mut text = String::new();
 
-            !invalid_jump
-        });
-    }
-    
-    jumps.retain(|_, v| !v.is_empty());
+    output::write_text(&mut text, &planned, universe, &request.filter, request.mode)?;
+
+    print!("{text}");
 
     Ok(())
 }
 
-fn get_shortest_path(jumps: &JumpMap, from: i64, to: i64, checker: &SystemValidityChecker) -> Option<Vec<(SystemId, SystemId, Jump)>> {
-    let mut parents = HashMap::<SystemId, (SystemId, Jump, i32)>::new();
-
-    let mut queue = VecDeque::new();
-
-    queue.push_back((from, 0));
-
-    while let Some((curr, curr_dist)) = queue.pop_front() {
-        if let Some(jumps) = jumps.get(&curr) {
-            for (neighbour, via) in jumps {
-                let n_valid = checker.is_system_valid(find_system_by_id(*neighbour));
-
-                let n_dist = curr_dist + if n_valid { 1 } else { 1000 };
+fn main() -> anyhow::Result<()> {
 
-                let n = parents.get(neighbour);
+    let args = Args::parse();
 
-                if n.is_none() || n_dist < n.unwrap().2 {
-                    parents.insert(*neighbour, (curr, *via, n_dist));
-                    queue.push_back((neighbour.clone(), n_dist));
-                }
-            }
-        }
+    // this has to happen before anything looks up a system
+    if let Some(sde) = &args.sde {
+        yaerp::sde_cache::load_and_install(sde)?;
     }
 
-    let mut path = Vec::new();
+    let mut base = match args.jump_drive {
+        Some(ship) => Universe::jump_drive(ship.get_range(args.jump_drive_calibration)),
+        None => Universe::stargates(),
+    };
 
-    let mut curr = to;
+    for (from, to) in parse_names(&args.removed_jumps, "--remove-jump", parse_removed_jump)? {
+        base.remove_jump(from, to);
+    }
 
-    while curr != from {
-        let parent = parents.get(&curr);
+    let parse_mode = if args.lenient { ParseMode::Lenient } else { ParseMode::Strict };
 
-        match parent {
-            Some((parent, via, _)) => {
-                path.push((curr, parent.clone(), *via));
-                curr = *parent;
-            },
-            None => {
-                return None;
-            },
-        }
-    }
+    let files = get_overlay_files(&args);
 
-    path.reverse();
+    match &args.command {
+        Some(Command::Serve { listen }) => {
+            let mut server = RouteServer::new(base, files, parse_mode)?;
 
-    Some(path)
-}
+            return yaerp::server::serve(listen, &mut server);
+        },
+        Some(Command::Shell) => {
+            let mut shell = Shell::new(OverlayUniverse::new(base, files, parse_mode)?, get_request(&args, Vec::new())?);
 
-#[derive(Debug)]
-struct Route {
-    pub start: SystemId,
-    pub end: SystemId,
-    pub jumps: Option<Vec<(SystemId, SystemId, Jump)>>,
-}
+            return shell.run();
+        },
+        Some(Command::Tui { waypoints }) => {
+            let waypoints = waypoints.iter()
+                .map(|s| find_system_by_name(s))
+                .collect::<Result<Vec<_>, _>>()?;
 
-fn main() -> anyhow::Result<()> {
+            let mut view = RouteView::new(OverlayUniverse::new(base, files, parse_mode)?, get_request(&args, waypoints)?);
 
-    let args = Args::parse();
+            return yaerp::tui::run(&mut view);
+        },
+        Some(Command::Near(_) | Command::Nearest { .. }) | None => {},
+    }
 
-    let mut jumps = JumpMap::new();
+    let mut universe = base;
 
-    load_stargates(&mut jumps);
+    let warnings = files.load_into(&mut universe, parse_mode, SystemTime::now())?;
 
-    for wh in &args.wormhole_bookmarks {
-        load_wormholes(std::fs::read_to_string(wh)?.as_str(), &mut jumps);
+    for warning in warnings {
+        eprintln!("warning: skipping {warning}\n");
     }
 
-    for ansi in &args.ansiblex_files {
-        load_ansiblexes(std::fs::read_to_string(ansi)?.as_str(), &mut jumps);
+    if let Some(Command::Near(options)) = &args.command {
+        return print_nearby(&args, options, &universe);
     }
 
-    let checker = SystemValidityChecker::new(&args)?;
-
-    if !args.no_filter {
-        filter_jumps(&mut jumps, &checker)?;
+    if let Some(Command::Nearest { from, criteria }) = &args.command {
+        return print_nearest(&args, from, criteria, &universe);
     }
 
-    let start = find_system_by_name(args.waypoints.first().unwrap())?;
-    let middle: Vec<_> = args.waypoints[1..args.waypoints.len()-1].iter().map(|s| find_system_by_name(s)).collect::<Result<Vec<_>, _>>()?;
-    let end = find_system_by_name(args.waypoints.last().unwrap())?;
-
-    let mut paths = Vec::new();
-
-    let potential_routes = if args.exact_route {
-        let mut systems = Vec::new();
-
-        systems.push(start);
-        systems.extend(middle.into_iter());
-        systems.push(end);
-
-        vec![systems]
-    } else {
-        middle.iter()
-            .permutations(middle.len())
-            .map(|middle| {
-                let mut systems = Vec::new();
-        
-                systems.push(start);
-                systems.extend(middle.into_iter().copied());
-                systems.push(end);
-
-                systems
-            })
-            .collect_vec()
-    };
+    let waypoints = args.waypoints.iter()
+        .map(|s| find_system_by_name(s))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    for systems in potential_routes.into_iter() {
-
-        let mut route = Vec::new();
-
-        for i in 0..systems.len() - 1 {
-            route.push(Route {
-                start: systems[i],
-                end: systems[i + 1],
-                jumps: get_shortest_path(&jumps, systems[i], systems[i + 1], &checker)
-            });
-        }
-    
-        let info = (
-            route.iter().all(|r| r.jumps.is_some()),
-            route.iter().map(|r| r.jumps.as_ref().map(|v| v.len()).unwrap_or_default()).sum::<usize>()
-        );
-
-        paths.push((
-            systems,
-            route,
-            info,
-        ));
-    }
+    let request = get_request(&args, waypoints)?;
 
-    let (systems, route, (valid, jumps)) = paths.into_iter()
-        .min_by_key(|(_, _, (valid, len))| (if *valid { 0 } else { 1 }, *len)).unwrap();
-
-    println!("\nBest route:");
-
-    for r in route {
-        if let Some(jumps) = r.jumps {
-            println!("\nFrom {} to {}: ({} jumps)", get_system_name(r.start), get_system_name(r.end), jumps.len());
-
-            for (to, from, via) in jumps {
-
-                let to_sys = find_system_by_id(to);
-
-                println!(
-                    "  {} -> {} ({:.2}, {}, via {}){}",
-                    get_system_name(from),
-                    get_system_name(to),
-                    to_sys.security,
-                    to_sys.region,
-                    match via {
-                        Jump::Wormhole => "wormhole",
-                        Jump::Gate => "gate",
-                        Jump::Ansiblex => "ansiblex",
-                    },
-                    match (checker.is_system_valid(find_system_by_id(from)), checker.is_system_valid(to_sys)) {
-                        (true, false) => "    Warning: entering filtered system",
-                        (false, false) => "    Warning: both systems are filtered out",
-                        (true, true) => "",
-                        (false, true) => ""
-                    }
-                );
-            }
-        } else {
-            println!("\nNo route from {} to {}", get_system_name(r.start), get_system_name(r.end));
-        }
-    }
+    let planned = request.plan(&universe)?;
 
-    println!("\nShorthand route:");
+    if args.format == OutputFormat::Json {
+        println!("{}", serde_json::to_string_pretty(&RouteReport::new(&planned, &universe, &request.filter))?);
 
-    for system in systems {
-        println!("  {}", get_system_name(system));
+        return Ok(());
     }
 
-    println!("\nTotal jumps: {jumps}");
+    let mut text = String::new();
 
-    if !valid {
-        println!("\nWarning: could not find a complete route; your restrictions are likely too strict");
-    }
+    output::write_text(&mut text, &planned, &universe, &request.filter, request.mode)?;
+
+    print!("{text}");
 
     Ok(())
 }
+
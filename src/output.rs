@@ -0,0 +1,581 @@
+use std::fmt;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{
+    fatigue, find_system_by_id, get_system_name, jump_drive,
+    route::get_crossing_time,
+    search::Cost,
+    systems::{System, SystemId},
+    wormhole::{MassState, ShipSize, WormholeInfo},
+    Jump, NearbySystem, PlannedRoute, RouteMode, SystemValidityChecker, Universe,
+};
+
+/// The version of the JSON output. This is bumped whenever a field is removed or changes meaning; new fields may be
+/// added without bumping it.
+pub const JSON_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct SystemInfo {
+    pub id: SystemId,
+    pub name: &'static str,
+    pub security: f32,
+    pub region_id: i64,
+    pub region: &'static str,
+}
+
+impl SystemInfo {
+    pub fn new(sys: &System) -> Self {
+        Self {
+            id: sys.id,
+            name: sys.name,
+            security: sys.security,
+            region_id: sys.region_id,
+            region: sys.region,
+        }
+    }
+
+    pub fn from_id(id: SystemId) -> Self {
+        Self::new(find_system_by_id(id))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WormholeDetails {
+    /// The bookmark to warp to in the system the jump starts in
+    pub bookmark: Option<String>,
+    /// The bookmark on the other end, in the system the jump leads to
+    pub far_bookmark: Option<String>,
+    /// The signature to scan down in the system the jump starts in
+    pub signature: Option<String>,
+    /// The wormhole type, like Q063
+    pub wh_type: Option<String>,
+    pub size: Option<ShipSize>,
+    /// An RFC 3339 time, if the expiry time is known
+    pub expires: Option<String>,
+    /// Tagged as end of life, or expiring within 4 hours
+    pub end_of_life: bool,
+    pub mass: Option<MassState>,
+    /// The file it was loaded from
+    pub source: Option<String>,
+}
+
+impl WormholeDetails {
+    pub fn new(info: &WormholeInfo, checker: &SystemValidityChecker) -> Self {
+        Self {
+            bookmark: info.bookmark.clone(),
+            far_bookmark: info.far_bookmark.clone(),
+            signature: info.signature.clone(),
+            wh_type: info.wh_type.clone(),
+            size: info.size,
+            expires: info.expires.map(|t| humantime::format_rfc3339_seconds(t).to_string()),
+            end_of_life: info.is_end_of_life(checker.wormholes.now),
+            mass: info.mass,
+            source: info.source.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct JumpInfo {
+    pub from: SystemInfo,
+    pub to: SystemInfo,
+    pub jump: Jump,
+    /// Set for wormholes that came with a bookmark, signature, size, expiry time, or mass state
+    pub wormhole: Option<WormholeDetails>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RemovedJumpInfo {
+    pub from: SystemInfo,
+    pub to: SystemInfo,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DetourInfo {
+    /// The removed jumps the leg would have taken
+    pub removed: Vec<RemovedJumpInfo>,
+    /// None when there is no route without the removed jumps
+    pub extra_cost: Option<Cost>,
+    pub extra_jumps: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LegInfo {
+    pub from: SystemInfo,
+    pub to: SystemInfo,
+    pub cost: Cost,
+    /// None when there is no route between the two waypoints
+    pub jumps: Option<Vec<JumpInfo>>,
+    /// Set when removed jumps made this leg longer, or impossible
+    pub detour: Option<DetourInfo>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarningKind {
+    EnteringFilteredSystem,
+    BothSystemsFiltered,
+    NoRoute,
+    Detour,
+    RiskyWormhole,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Warning {
+    pub kind: WarningKind,
+    /// The system the warning is about, if any
+    pub system: Option<SystemId>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RouteReport {
+    pub version: u32,
+    /// The waypoints in the order they are visited
+    pub waypoints: Vec<SystemInfo>,
+    pub legs: Vec<LegInfo>,
+    pub total_jumps: usize,
+    pub total_cost: Cost,
+    /// False if any leg has no route
+    pub valid: bool,
+    pub warnings: Vec<Warning>,
+}
+
+impl RouteReport {
+    /// `universe` is the one the route was planned in, which has the details of its wormholes.
+    pub fn new(route: &PlannedRoute, universe: &Universe, checker: &SystemValidityChecker) -> Self {
+        let mut warnings = Vec::new();
+
+        let legs = route.legs.iter()
+            .map(|r| {
+                if r.jumps.is_none() {
+                    warnings.push(Warning {
+                        kind: WarningKind::NoRoute,
+                        system: Some(r.end),
+                        message: format!("no route from {} to {}", find_system_by_id(r.start).name, find_system_by_id(r.end).name),
+                    });
+                }
+
+                let jumps = r.jumps.as_ref().map(|path| {
+                    path.iter()
+                        .map(|(to, from, via)| {
+                            let from_sys = find_system_by_id(*from);
+                            let to_sys = find_system_by_id(*to);
+
+                            let kind = match (checker.is_system_valid(from_sys), checker.is_system_valid(to_sys)) {
+                                (true, false) => Some((WarningKind::EnteringFilteredSystem, "entering filtered system")),
+                                (false, false) => Some((WarningKind::BothSystemsFiltered, "both systems are filtered out")),
+                                _ => None,
+                            };
+
+                            if let Some((kind, message)) = kind {
+                                warnings.push(Warning {
+                                    kind,
+                                    system: Some(*to),
+                                    message: format!("{} -> {}: {message}", from_sys.name, to_sys.name),
+                                });
+                            }
+
+                            let wormhole = universe.get_extra_jump(*from, *to, *via).and_then(|j| j.wormhole.as_ref());
+
+                            let risks = wormhole.map(|info| info.get_risks(checker.wormholes.now)).unwrap_or_default();
+
+                            if !risks.is_empty() {
+                                warnings.push(Warning {
+                                    kind: WarningKind::RiskyWormhole,
+                                    system: Some(*to),
+                                    message: format!("{} -> {}: wormhole is {}", from_sys.name, to_sys.name, risks.join(" and ")),
+                                });
+                            }
+
+                            JumpInfo {
+                                from: SystemInfo::new(from_sys),
+                                to: SystemInfo::new(to_sys),
+                                jump: *via,
+                                wormhole: wormhole.map(|info| WormholeDetails::new(info, checker)),
+                            }
+                        })
+                        .collect()
+                });
+
+                let detour = r.detour.as_ref().map(|d| {
+                    warnings.push(Warning {
+                        kind: WarningKind::Detour,
+                        system: Some(r.end),
+                        message: d.describe(),
+                    });
+
+                    DetourInfo {
+                        removed: d.removed.iter()
+                            .map(|(from, to)| RemovedJumpInfo { from: SystemInfo::from_id(*from), to: SystemInfo::from_id(*to) })
+                            .collect(),
+                        extra_cost: d.extra_cost,
+                        extra_jumps: d.extra_jumps,
+                    }
+                });
+
+                LegInfo {
+                    from: SystemInfo::from_id(r.start),
+                    to: SystemInfo::from_id(r.end),
+                    cost: r.cost,
+                    jumps,
+                    detour,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            version: JSON_FORMAT_VERSION,
+            waypoints: route.waypoints.iter().map(|id| SystemInfo::from_id(*id)).collect(),
+            total_jumps: legs.iter().map(|l| l.jumps.as_ref().map(|j| j.len()).unwrap_or_default()).sum(),
+            total_cost: legs.iter().map(|l| l.cost).sum(),
+            valid: legs.iter().all(|l| l.jumps.is_some()),
+            legs,
+            warnings,
+        }
+    }
+}
+
+/// Writes a route the way the command line prints it.
+///
+/// `universe` is the one the route was planned in, and `mode` the mode it was planned with, which decides whether
+/// jump timers or travel times are shown.
+pub fn write_text(out: &mut impl fmt::Write, planned: &PlannedRoute, universe: &Universe, checker: &SystemValidityChecker, mode: RouteMode) -> fmt::Result {
+    let valid = planned.is_valid();
+    let jumps = planned.get_jump_count();
+    let cost = planned.get_cost();
+    let start_timers = planned.start_timers;
+    let systems = &planned.waypoints;
+    let route = &planned.legs;
+
+    writeln!(out, "\nBest route:")?;
+
+    let mut timers = start_timers;
+
+    // seconds since the start of the route, for warp routes
+    let mut elapsed = 0.0;
+
+    for r in route {
+        if let Some(jumps) = &r.jumps {
+            let crossing_times = match mode {
+                RouteMode::Warp(profile) => {
+                    let mut arrival = None;
+
+                    jumps.iter()
+                        .map(|(to, from, via)| {
+                            let time = get_crossing_time(&profile, *from, arrival, *to, *via);
+                            arrival = Some((*from, *via));
+                            time
+                        })
+                        .collect_vec()
+                },
+                _ => Vec::new(),
+            };
+
+            writeln!(
+                out,
+                "\nFrom {} to {}: ({} jumps, cost {:.2}{})",
+                get_system_name(r.start),
+                get_system_name(r.end),
+                jumps.len(),
+                r.cost,
+                match mode {
+                    RouteMode::Warp(_) => format!(", about {}", fatigue::format_duration(crossing_times.iter().sum::<f64>() / 60.0)),
+                    _ => String::new(),
+                },
+            )?;
+
+            if let Some(detour) = &r.detour {
+                writeln!(out, "  Detour: {}", detour.describe())?;
+            }
+
+            for (i, (to, from, via)) in jumps.iter().copied().enumerate() {
+
+                let to_sys = find_system_by_id(to);
+
+                let extra = universe.get_extra_jump(from, to, via);
+
+                let note = extra
+                    .and_then(|j| j.note.as_ref())
+                    .map(|note| format!(" \"{note}\""))
+                    .unwrap_or_default();
+
+                let wormhole = extra.and_then(|j| j.wormhole.as_ref());
+
+                let wormhole_info = wormhole
+                    .map(|info| format!(", {}", info.describe(checker.wormholes.now)))
+                    .unwrap_or_default();
+
+                let wormhole_risks = wormhole
+                    .map(|info| info.get_risks(checker.wormholes.now))
+                    .filter(|risks| !risks.is_empty())
+                    .map(|risks| format!("    Warning: wormhole is {}", risks.join(" and ")))
+                    .unwrap_or_default();
+
+                writeln!(
+                    out,
+                    "  {} -> {} ({:.2}, {}, via {}{}{}{}){}{}",
+                    get_system_name(from),
+                    get_system_name(to),
+                    to_sys.security,
+                    to_sys.region,
+                    via.name(),
+                    note,
+                    wormhole_info,
+                    match (via, mode) {
+                        (Jump::Cyno, RouteMode::JumpDrive(ship)) => {
+                            let ly = jump_drive::get_distance_ly(find_system_by_id(from), to_sys);
+
+                            timers = timers.jump(ly, ship.get_fatigue_reduction());
+
+                            format!(
+                                ", {:.2} LY, fatigue {}, cooldown {}",
+                                ly,
+                                fatigue::format_duration(timers.get_fatigue()),
+                                fatigue::format_duration(timers.get_cooldown()),
+                            )
+                        },
+                        (_, RouteMode::Warp(_)) => {
+                            elapsed += crossing_times[i];
+
+                            format!(", ETA {}", fatigue::format_duration(elapsed / 60.0))
+                        },
+                        _ => String::new(),
+                    },
+                    match (checker.is_system_valid(find_system_by_id(from)), checker.is_system_valid(to_sys)) {
+                        (true, false) => "    Warning: entering filtered system",
+                        (false, false) => "    Warning: both systems are filtered out",
+                        (true, true) => "",
+                        (false, true) => ""
+                    },
+                    wormhole_risks,
+                )?;
+            }
+        } else {
+            writeln!(out, "\nNo route from {} to {}", get_system_name(r.start), get_system_name(r.end))?;
+
+            if let Some(detour) = &r.detour {
+                writeln!(out, "  Detour: {}", detour.describe())?;
+            }
+        }
+    }
+
+    writeln!(out, "\nShorthand route:")?;
+
+    for system in systems {
+        writeln!(out, "  {}", get_system_name(*system))?;
+    }
+
+    writeln!(out, "\nTotal jumps: {jumps}")?;
+    writeln!(out, "Total cost: {cost:.2}")?;
+
+    match mode {
+        RouteMode::JumpDrive(_) => {
+            writeln!(out, "Total time: {} (waiting on jump timers)", fatigue::format_duration(timers.time - start_timers.time))?;
+            writeln!(out, "Jump fatigue on arrival: {}", fatigue::format_duration(timers.get_fatigue()))?;
+        },
+        RouteMode::Warp(_) => {
+            writeln!(out, "Total time: {} (estimated)", fatigue::format_duration(elapsed / 60.0))?;
+        },
+        RouteMode::Jumps => {},
+    }
+
+    if !valid {
+        writeln!(out, "\nWarning: could not find a complete route; your restrictions are likely too strict")?;
+    }
+
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct NearbyInfo {
+    pub system: SystemInfo,
+    pub jumps: usize,
+    /// The kinds of jump taken on the way
+    pub via: Vec<Jump>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NearbyReport {
+    pub version: u32,
+    pub from: SystemInfo,
+    pub max_jumps: usize,
+    pub systems: Vec<NearbyInfo>,
+}
+
+impl NearbyReport {
+    pub fn new(from: SystemId, max_jumps: usize, nearby: &[NearbySystem]) -> Self {
+        Self {
+            version: JSON_FORMAT_VERSION,
+            from: SystemInfo::from_id(from),
+            max_jumps,
+            systems: nearby.iter()
+                .map(|sys| NearbyInfo { system: SystemInfo::from_id(sys.id), jumps: sys.jumps, via: sys.via.clone() })
+                .collect(),
+        }
+    }
+}
+
+/// Writes the systems near `from` as a table, in the order they're given.
+pub fn write_nearby_text(out: &mut impl fmt::Write, from: SystemId, max_jumps: usize, nearby: &[NearbySystem]) -> fmt::Result {
+    writeln!(out, "{} systems within {max_jumps} jumps of {}:", nearby.len(), get_system_name(from))?;
+
+    let systems = nearby.iter().map(|sys| find_system_by_id(sys.id)).collect_vec();
+
+    let name_width = systems.iter().map(|sys| sys.name.len()).max().unwrap_or(0).max("System".len());
+    let region_width = systems.iter().map(|sys| sys.region.len()).max().unwrap_or(0).max("Region".len());
+
+    writeln!(out, "  Jumps  Security  {:name_width$}  {:region_width$}  Via", "System", "Region")?;
+
+    for (near, sys) in nearby.iter().zip(systems) {
+        writeln!(
+            out,
+            "  {:>5}  {:>8.2}  {:name_width$}  {:region_width$}  {}",
+            near.jumps,
+            sys.security,
+            sys.name,
+            sys.region,
+            near.via.iter().map(|j| j.name()).join(", "),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jump_types_serialize_as_their_names() {
+        for jump in Jump::ALL {
+            assert_eq!(serde_json::to_value(jump).unwrap(), jump.name());
+        }
+    }
+
+    #[test]
+    fn empty_routes_are_valid() {
+        let route = PlannedRoute {
+            waypoints: Vec::new(),
+            legs: Vec::new(),
+            start_timers: crate::JumpTimers::new(0.0),
+        };
+
+        let report = serde_json::to_value(RouteReport::new(&route, &Universe::default(), &SystemValidityChecker::default())).unwrap();
+
+        assert_eq!(report["version"], JSON_FORMAT_VERSION);
+        assert_eq!(report["valid"], true);
+        assert_eq!(report["total_jumps"], 0);
+        assert_eq!(report["warnings"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn route_reports_keep_their_shape() {
+        use std::time::Duration;
+
+        use serde_json::json;
+
+        use crate::route::Leg;
+
+        let jita = crate::find_system_by_name("Jita").unwrap();
+        let thera = crate::find_system_by_name("Thera").unwrap();
+        let perimeter = crate::find_system_by_name("Perimeter").unwrap();
+
+        let mut checker = SystemValidityChecker::default();
+        checker.wormholes.now = humantime::parse_rfc3339("2024-05-01T12:00:00Z").unwrap();
+
+        let mut universe = Universe::default();
+
+        universe.add_wormhole(jita, thera, WormholeInfo {
+            signature: Some("ABC-123".to_owned()),
+            size: Some(ShipSize::Large),
+            expires: Some(checker.wormholes.now + Duration::from_secs(2 * 60 * 60)),
+            mass: Some(MassState::Critical),
+            ..Default::default()
+        });
+
+        let timers = crate::JumpTimers::new(0.0);
+
+        let leg = |start, end, cost, jumps| Leg { start, end, cost, jumps: Some(jumps), timers, detour: None };
+
+        let route = PlannedRoute {
+            waypoints: vec![jita, thera, perimeter],
+            legs: vec![
+                leg(jita, thera, 1.0, vec![(thera, jita, Jump::Wormhole)]),
+                leg(thera, perimeter, 2.0, vec![(jita, thera, Jump::Wormhole), (perimeter, jita, Jump::Gate)]),
+            ],
+            start_timers: timers,
+        };
+
+        let system = |id| serde_json::to_value(SystemInfo::from_id(id)).unwrap();
+
+        let wormhole = json!({
+            "bookmark": null,
+            "far_bookmark": null,
+            "signature": "ABC-123",
+            "wh_type": null,
+            "size": "large",
+            "expires": "2024-05-01T14:00:00Z",
+            "end_of_life": true,
+            "mass": "critical",
+            "source": null,
+        });
+
+        let expected = json!({
+            "version": JSON_FORMAT_VERSION,
+            "waypoints": [system(jita), system(thera), system(perimeter)],
+            "legs": [
+                {
+                    "from": system(jita),
+                    "to": system(thera),
+                    "cost": 1.0,
+                    "jumps": [
+                        { "from": system(jita), "to": system(thera), "jump": "wormhole", "wormhole": wormhole },
+                    ],
+                    "detour": null,
+                },
+                {
+                    "from": system(thera),
+                    "to": system(perimeter),
+                    "cost": 2.0,
+                    "jumps": [
+                        { "from": system(thera), "to": system(jita), "jump": "wormhole", "wormhole": wormhole },
+                        { "from": system(jita), "to": system(perimeter), "jump": "gate", "wormhole": null },
+                    ],
+                    "detour": null,
+                },
+            ],
+            "total_jumps": 3,
+            "total_cost": 3.0,
+            "valid": true,
+            "warnings": [
+                { "kind": "risky-wormhole", "system": thera, "message": "Jita -> Thera: wormhole is end of life and mass critical" },
+                { "kind": "risky-wormhole", "system": jita, "message": "Thera -> Jita: wormhole is end of life and mass critical" },
+            ],
+        });
+
+        assert_eq!(serde_json::to_value(RouteReport::new(&route, &universe, &checker)).unwrap(), expected);
+    }
+
+    #[test]
+    fn nearby_systems_line_up() {
+        let jita = crate::find_system_by_name("Jita").unwrap();
+        let perimeter = crate::find_system_by_name("Perimeter").unwrap();
+
+        let nearby = [NearbySystem { id: perimeter, jumps: 1, via: vec![Jump::Gate] }];
+
+        let mut text = String::new();
+
+        write_nearby_text(&mut text, jita, 3, &nearby).unwrap();
+
+        assert_eq!(text, "1 systems within 3 jumps of Jita:\n  Jumps  Security  System     Region     Via\n      1      0.95  Perimeter  The Forge  gate\n");
+
+        let report = serde_json::to_value(NearbyReport::new(jita, 3, &nearby)).unwrap();
+
+        assert_eq!(report["systems"][0]["system"]["name"], "Perimeter");
+        assert_eq!(report["systems"][0]["via"], serde_json::json!(["gate"]));
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::Context;
+
+use crate::{
+    diagnostics::{Diagnostic, ParseMode},
+    universe::Universe,
+    ConnectionFormat,
+};
+
+/// The files extra jumps are loaded from, kept so they can be loaded again when they change.
+#[derive(Debug, Clone, Default)]
+pub struct OverlayFiles {
+    /// Copy+pasted bookmarks
+    pub wormholes: Vec<PathBuf>,
+    pub ansiblexes: Vec<PathBuf>,
+    pub eve_scout: Vec<PathBuf>,
+    /// Tripwire, Pathfinder, or Wanderer exports
+    pub chains: Vec<PathBuf>,
+    pub connections: Vec<PathBuf>,
+    pub removed_jumps: Vec<PathBuf>,
+}
+
+fn read(path: &Path) -> anyhow::Result<(String, String)> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+
+    Ok((path.display().to_string(), content))
+}
+
+impl OverlayFiles {
+    pub fn is_empty(&self) -> bool {
+        self.get_paths().next().is_none()
+    }
+
+    pub fn get_paths(&self) -> impl Iterator<Item = &PathBuf> + '_ {
+        self.wormholes.iter()
+            .chain(&self.ansiblexes)
+            .chain(&self.eve_scout)
+            .chain(&self.chains)
+            .chain(&self.connections)
+            .chain(&self.removed_jumps)
+    }
+
+    /// When each file was last changed, or None if it can't be read.
+    pub fn get_modified_times(&self) -> Vec<Option<SystemTime>> {
+        self.get_paths()
+            .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    /// Loads every file on top of `universe`. Connections and wormholes that expired before `now` are left out.
+    ///
+    /// Returns the lines that were skipped in lenient mode.
+    pub fn load_into(&self, universe: &mut Universe, mode: ParseMode, now: SystemTime) -> anyhow::Result<Vec<Diagnostic>> {
+        let mut warnings = Vec::new();
+
+        for path in &self.wormholes {
+            let (file, content) = read(path)?;
+
+            warnings.extend(universe.load_wormholes(&file, &content, mode)?);
+        }
+
+        for path in &self.ansiblexes {
+            let (file, content) = read(path)?;
+
+            warnings.extend(universe.load_ansiblexes(&file, &content, mode)?);
+        }
+
+        for path in &self.eve_scout {
+            let (file, content) = read(path)?;
+
+            warnings.extend(universe.load_eve_scout(&file, &content, mode, now)?);
+        }
+
+        for path in &self.chains {
+            let (file, content) = read(path)?;
+
+            let (_, chain_warnings) = universe.load_chain(&file, &content, mode, now)?;
+
+            warnings.extend(chain_warnings);
+        }
+
+        for path in &self.connections {
+            let (file, content) = read(path)?;
+
+            warnings.extend(universe.load_connections(&file, &content, ConnectionFormat::from_path(path), mode, now)?);
+        }
+
+        for path in &self.removed_jumps {
+            let (file, content) = read(path)?;
+
+            warnings.extend(universe.load_removed_jumps(&file, &content, mode)?);
+        }
+
+        Ok(warnings)
+    }
+}
+
+/// A universe with overlay files loaded on top, which can be loaded again when they change.
+#[derive(Debug, Clone)]
+pub struct OverlayUniverse {
+    /// The universe before any overlay files are loaded
+    base: Universe,
+    files: OverlayFiles,
+    mode: ParseMode,
+    universe: Universe,
+    /// When each overlay file had last changed when it was loaded
+    loaded: Vec<Option<SystemTime>>,
+}
+
+impl OverlayUniverse {
+    /// Loads `files` on top of `base`.
+    pub fn new(base: Universe, files: OverlayFiles, mode: ParseMode) -> anyhow::Result<Self> {
+        let mut overlays = Self {
+            universe: base.clone(),
+            base,
+            files,
+            mode,
+            loaded: Vec::new(),
+        };
+
+        overlays.reload()?;
+
+        Ok(overlays)
+    }
+
+    /// Loads the overlay files again. The old universe is kept if they can't be loaded.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        let modified = self.files.get_modified_times();
+
+        let mut universe = self.base.clone();
+
+        // remember the attempt, so that a broken file is only reported once
+        self.loaded = modified;
+
+        for warning in self.files.load_into(&mut universe, self.mode, SystemTime::now())? {
+            eprintln!("warning: skipping {warning}\n");
+        }
+
+        self.universe = universe;
+
+        Ok(())
+    }
+
+    /// Reloads the overlay files if any of them changed since they were last loaded.
+    pub fn reload_if_changed(&mut self) {
+        if self.files.get_modified_times() == self.loaded {
+            return;
+        }
+
+        match self.reload() {
+            Ok(()) => eprintln!("reloaded the overlay files"),
+            Err(e) => eprintln!("error: could not reload the overlay files, keeping the old ones: {e:#}"),
+        }
+    }
+
+    /// Adds a file of copy+pasted wormhole bookmarks, and loads it. The file isn't kept if it can't be loaded.
+    pub fn add_wormholes(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        self.files.wormholes.push(path);
+
+        if let Err(e) = self.reload() {
+            self.files.wormholes.pop();
+            self.loaded = self.files.get_modified_times();
+
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    pub fn get_files(&self) -> &OverlayFiles {
+        &self.files
+    }
+
+    pub fn get_universe(&self) -> &Universe {
+        &self.universe
+    }
+}
@@ -0,0 +1,928 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::{anyhow, bail, Context};
+use clap::ValueEnum;
+use itertools::Itertools;
+
+use crate::{
+    fatigue::{self, JumpTimers, TimedPath},
+    filter::SystemValidityChecker,
+    find_system_by_id, find_system_by_index, get_system_name,
+    jump_drive::{self, ShipClass},
+    search::{self, Cost, DenseParents, Hop, ShortestPaths},
+    systems::{self, System, SystemId, SystemIndex},
+    universe::Universe,
+    warp::WarpProfile,
+    Jump, SystemSecurity,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RoutePreference {
+    /// Prefer the fewest jumps
+    Shorter,
+    /// Prefer staying in highsec, avoiding lowsec, nullsec, and j-space
+    Safer,
+    /// Prefer staying out of highsec
+    LessSecure,
+}
+
+/// The extra cost of entering a filtered system, relative to a normal jump.
+pub const FILTERED_JUMP_PENALTY: Cost = 1000.0;
+
+/// The extra cost of entering a system the route preference avoids, per point of security.
+///
+/// With `--prefer safer`, entering a 0.4 system costs an extra 60 and a -1.0 system an extra 200.
+/// With `--prefer less-secure`, entering a 1.0 system costs an extra 100.
+pub const SECURITY_PREFERENCE_PENALTY: Cost = 100.0;
+
+/// The seconds added to a timed route for each point of penalty, about as long as crossing a system takes.
+///
+/// Penalties are added on top of the travel time rather than scaling it, so that a filtered system is as bad to pass
+/// through quickly as slowly.
+pub const PENALTY_SECONDS: Cost = 60.0;
+
+#[derive(Debug, Clone)]
+pub struct CostModel {
+    pub jump_costs: HashMap<Jump, Cost>,
+    pub preference: RoutePreference,
+}
+
+impl Default for CostModel {
+    fn default() -> Self {
+        Self::new(&[], RoutePreference::Shorter)
+    }
+}
+
+impl CostModel {
+    /// Every jump costs 1, unless it's given a different cost in `jump_costs`.
+    pub fn new(jump_costs: &[(Jump, Cost)], preference: RoutePreference) -> Self {
+        let mut costs = Jump::ALL.into_iter().map(|j| (j, 1.0)).collect::<HashMap<_, _>>();
+
+        costs.extend(jump_costs.iter().copied());
+
+        Self {
+            jump_costs: costs,
+            preference,
+        }
+    }
+
+    pub fn get_security_penalty(&self, to: &System) -> Cost {
+        let security = to.security as Cost;
+
+        match (self.preference, to.get_security_class()) {
+            (RoutePreference::Shorter, _) => 0.0,
+            (RoutePreference::Safer, SystemSecurity::Highsec) => 0.0,
+            (RoutePreference::Safer, _) => SECURITY_PREFERENCE_PENALTY * (1.0 - security),
+            (RoutePreference::LessSecure, SystemSecurity::Highsec) => SECURITY_PREFERENCE_PENALTY * security,
+            (RoutePreference::LessSecure, _) => 0.0,
+        }
+    }
+
+    pub fn get_jump_cost(&self, to: &System, via: Jump, checker: &SystemValidityChecker) -> Cost {
+        self.get_cost_from_base(to, self.jump_costs[&via], checker)
+    }
+
+    /// Like `get_jump_cost`, for a jump with its own cost instead of the cost of its kind.
+    pub fn get_cost_from_base(&self, to: &System, base: Cost, checker: &SystemValidityChecker) -> Cost {
+        base + self.get_penalty(to, checker)
+    }
+
+    /// The cost of entering `to` on top of the jump itself, from the route preference and the filters.
+    pub fn get_penalty(&self, to: &System, checker: &SystemValidityChecker) -> Cost {
+        let penalty = self.get_security_penalty(to);
+
+        if checker.is_system_valid(to) {
+            penalty
+        } else {
+            penalty + FILTERED_JUMP_PENALTY
+        }
+    }
+}
+
+/// Parses a jump cost written as `type=cost`, like `wormhole=3`.
+pub fn parse_jump_cost(arg: &str) -> anyhow::Result<(Jump, Cost)> {
+    let (kind, cost) = arg.split_once('=').ok_or_else(|| anyhow!("expected type=cost, got '{arg}'"))?;
+
+    let kind = kind.trim().to_lowercase();
+
+    let jump = Jump::ALL.into_iter()
+        .find(|j| j.name() == kind)
+        .ok_or_else(|| anyhow!("unknown jump type '{kind}': expected one of {}", Jump::ALL.iter().map(|j| j.name()).join(", ")))?;
+
+    let cost = cost.trim().parse::<Cost>().with_context(|| format!("invalid cost '{cost}'"))?;
+
+    if !cost.is_finite() || cost < 0.0 {
+        bail!("jump costs must be zero or positive, got {cost}");
+    }
+
+    Ok((jump, cost))
+}
+
+/// The cost given to a leg that has no route, so that incomplete routes are only picked when nothing else works.
+pub const UNREACHABLE_LEG_COST: Cost = 1e12;
+
+/// The hops of a path, as `(to, from, via)`.
+pub type Path = Vec<(SystemId, SystemId, Jump)>;
+
+/// The hops of a path through system indices, which is what the searches work on.
+type IndexPath = Vec<Hop<SystemIndex, Jump>>;
+
+fn to_path(hops: IndexPath) -> Path {
+    hops.into_iter()
+        .map(|(to, from, via)| (find_system_by_index(to).id, find_system_by_index(from).id, via))
+        .collect()
+}
+
+fn get_edges<'a>(universe: &'a Universe, curr: SystemIndex, checker: &'a SystemValidityChecker, costs: &'a CostModel) -> impl Iterator<Item = (SystemIndex, Jump, Cost)> + 'a {
+    get_priced_edges(universe, curr, checker, costs).map(|(neighbour, via, base, penalty)| (neighbour, via, base + penalty))
+}
+
+/// Like `get_edges`, with each jump's own cost and its penalty kept apart.
+fn get_priced_edges<'a>(universe: &'a Universe, curr: SystemIndex, checker: &'a SystemValidityChecker, costs: &'a CostModel) -> impl Iterator<Item = (SystemIndex, Jump, Cost, Cost)> + 'a {
+    let curr_sys = find_system_by_index(curr);
+
+    universe.get_jumps_from(curr)
+        .filter(move |(neighbour, _, _)| checker.is_jump_allowed(curr_sys, find_system_by_index(*neighbour)))
+        .filter(|(_, _, extra)| extra.is_none_or(|j| !j.is_expired(checker.wormholes.now)))
+        .filter(|(_, _, extra)| extra.and_then(|j| j.wormhole.as_ref()).is_none_or(|info| checker.is_wormhole_allowed(info)))
+        .map(|(neighbour, via, extra)| {
+            let base = extra.and_then(|j| j.cost).unwrap_or(costs.jump_costs[&via]);
+
+            (neighbour, via, base, costs.get_penalty(find_system_by_index(neighbour), checker))
+        })
+}
+
+/// Finds the path with the lowest cost to whichever system `is_target` accepts first.
+fn get_shortest_path(universe: &Universe, from: SystemIndex, is_target: impl Fn(SystemIndex) -> bool, checker: &SystemValidityChecker, costs: &CostModel) -> Option<(Cost, IndexPath)> {
+    let mut reached = None;
+
+    let paths: ShortestPaths<_, _, DenseParents<_>> = search::dijkstra_with(from, |curr| get_edges(universe, curr, checker, costs), |curr| {
+        if is_target(curr) {
+            reached = Some(curr);
+        }
+
+        reached.is_some()
+    });
+
+    let reached = reached?;
+
+    Some((paths.cost(reached)?, paths.path(reached)?))
+}
+
+/// Finds the path with the lowest cost plus minutes spent waiting on jump timers, starting with the given timers.
+fn get_fastest_jump_path(universe: &Universe, from: SystemIndex, is_target: impl Fn(SystemIndex) -> bool, checker: &SystemValidityChecker, costs: &CostModel, ship: ShipClass, timers: JumpTimers) -> Option<TimedPath<SystemIndex, Jump>> {
+    fatigue::fastest_path_to_any(from, is_target, timers, ship.get_fatigue_reduction(), |curr| {
+        get_edges(universe, curr, checker, costs).map(move |(neighbour, via, cost)| {
+            let ly = match via {
+                Jump::Cyno => Some(jump_drive::get_distance_ly(find_system_by_index(curr), find_system_by_index(neighbour))),
+                _ => None,
+            };
+
+            (neighbour, via, cost, ly)
+        })
+    })
+}
+
+/// A system on a timed route, and the system and jump it was entered from. The time spent in a system depends on
+/// which gate the ship arrives at, so timed searches run over these instead of plain systems.
+type WarpState = (SystemIndex, Option<(SystemIndex, Jump)>);
+
+/// The position of the gate in `sys` that leads to `other`, if `via` is a gate.
+fn get_gate_position(sys: &System, other: SystemId, via: Jump) -> Option<[f64; 3]> {
+    if via != Jump::Gate {
+        return None;
+    }
+
+    sys.gates.iter().find(|g| g.destination == other).map(|g| g.position)
+}
+
+fn get_system_crossing_time(profile: &WarpProfile, sys: &System, arrival: Option<(SystemId, Jump)>, next: SystemId, via: Jump) -> f64 {
+    let entry = arrival.and_then(|(prev, prev_via)| get_gate_position(sys, prev, prev_via));
+    let exit = get_gate_position(sys, next, via);
+
+    profile.get_crossing_time(entry, exit)
+}
+
+/// The seconds spent crossing `curr` to jump to `next`, after arriving from `arrival` (or undocking, if None).
+pub fn get_crossing_time(profile: &WarpProfile, curr: SystemId, arrival: Option<(SystemId, Jump)>, next: SystemId, via: Jump) -> f64 {
+    get_system_crossing_time(profile, find_system_by_id(curr), arrival, next, via)
+}
+
+/// The cost of a jump on a timed route: the seconds it takes times the jump's own cost, plus `PENALTY_SECONDS` for
+/// each point of penalty.
+fn get_timed_cost(base: Cost, penalty: Cost, seconds: f64) -> Cost {
+    base * seconds + penalty * PENALTY_SECONDS
+}
+
+/// Like `get_edges`, but each jump is costed by `get_timed_cost`.
+fn get_timed_edges<'a>(universe: &'a Universe, (curr, arrival): WarpState, checker: &'a SystemValidityChecker, costs: &'a CostModel, profile: &'a WarpProfile) -> impl Iterator<Item = (WarpState, Jump, Cost)> + 'a {
+    let sys = find_system_by_index(curr);
+    let arrival_id = arrival.map(|(prev, via)| (find_system_by_index(prev).id, via));
+
+    get_priced_edges(universe, curr, checker, costs).map(move |(next, via, base, penalty)| {
+        let time = get_system_crossing_time(profile, sys, arrival_id, find_system_by_index(next).id, via);
+
+        ((next, Some((curr, via))), via, get_timed_cost(base, penalty, time))
+    })
+}
+
+/// Finds the path with the lowest estimated travel time (scaled by the jump costs). The route starts at an unknown
+/// position in `from`, as if the ship had just undocked.
+fn get_quickest_path(universe: &Universe, from: SystemIndex, is_target: impl Fn(SystemIndex) -> bool, checker: &SystemValidityChecker, costs: &CostModel, profile: &WarpProfile) -> Option<(Cost, IndexPath)> {
+    let mut reached = None;
+
+    let paths = search::dijkstra((from, None), |state| get_timed_edges(universe, state, checker, costs, profile), |state| {
+        if is_target(state.0) {
+            reached = Some(state);
+        }
+
+        reached.is_some()
+    });
+
+    let reached = reached?;
+
+    let path = paths.path(reached)?
+        .into_iter()
+        .map(|((to, _), (from, _), via)| (to, from, via))
+        .collect();
+
+    Some((paths.cost(reached)?, path))
+}
+
+/// A system found by [`find_nearby`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NearbySystem {
+    pub id: SystemId,
+    /// The fewest jumps it takes to get there
+    pub jumps: usize,
+    /// The kinds of jump taken on the way, from the shortest path that needs the fewest kinds
+    pub via: Vec<Jump>,
+}
+
+/// Every system within `max_jumps` of `from` (not counting `from` itself). Jumps the filter removes aren't taken.
+/// Sorted nearest first, then by name.
+pub fn find_nearby(universe: &Universe, from: SystemId, max_jumps: usize, checker: &SystemValidityChecker) -> anyhow::Result<Vec<NearbySystem>> {
+    let start = systems::get_system_index(from).with_context(|| format!("system {from} doesn't exist"))?;
+
+    let costs = CostModel::default();
+
+    // the jumps to each system found so far, and the kinds of jump on the way
+    let mut found = HashMap::from([(start, (0, Vec::new()))]);
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(curr) = queue.pop_front() {
+        let (jumps, via) = found[&curr].clone();
+
+        if jumps == max_jumps {
+            continue;
+        }
+
+        for (neighbour, kind, _) in get_edges(universe, curr, checker, &costs) {
+            let mut next_via = via.clone();
+
+            if !next_via.contains(&kind) {
+                next_via.push(kind);
+                next_via.sort();
+            }
+
+            match found.get_mut(&neighbour) {
+                None => {
+                    found.insert(neighbour, (jumps + 1, next_via));
+                    queue.push_back(neighbour);
+                },
+                // systems the same number of jumps away haven't been searched from yet, so a simpler path can
+                // still replace the one they were found with
+                Some((other_jumps, other_via)) if *other_jumps == jumps + 1 && next_via.len() < other_via.len() => {
+                    *other_via = next_via;
+                },
+                Some(_) => {},
+            }
+        }
+    }
+
+    found.remove(&start);
+
+    let nearby = found.into_iter()
+        .map(|(idx, (jumps, via))| NearbySystem { id: find_system_by_index(idx).id, jumps, via })
+        .sorted_by_key(|sys| (sys.jumps, get_system_name(sys.id)))
+        .collect();
+
+    Ok(nearby)
+}
+
+/// What routes are optimized for.
+#[derive(Debug, Clone, Copy)]
+pub enum RouteMode {
+    /// The lowest total cost, which is the fewest jumps by default
+    Jumps,
+    /// The lowest estimated travel time, from the warps between gates
+    Warp(WarpProfile),
+    /// The lowest total cost plus time spent waiting on jump timers. The universe must be built with
+    /// `Universe::jump_drive`.
+    JumpDrive(ShipClass),
+}
+
+/// Finds the best path for one leg of the route. Returns its cost, the path, and the jump timers on arrival.
+fn get_leg(universe: &Universe, from: SystemIndex, to: SystemIndex, checker: &SystemValidityChecker, costs: &CostModel, mode: RouteMode, timers: JumpTimers) -> Option<TimedPath<SystemIndex, Jump>> {
+    get_leg_to_any(universe, from, |idx| idx == to, checker, costs, mode, timers)
+}
+
+/// Like `get_leg`, to whichever system `is_target` accepts first.
+fn get_leg_to_any(universe: &Universe, from: SystemIndex, is_target: impl Fn(SystemIndex) -> bool, checker: &SystemValidityChecker, costs: &CostModel, mode: RouteMode, timers: JumpTimers) -> Option<TimedPath<SystemIndex, Jump>> {
+    match mode {
+        RouteMode::Jumps => get_shortest_path(universe, from, is_target, checker, costs).map(|(cost, path)| (cost, path, timers)),
+        RouteMode::Warp(profile) => get_quickest_path(universe, from, is_target, checker, costs, &profile).map(|(cost, path)| (cost, path, timers)),
+        RouteMode::JumpDrive(ship) => get_fastest_jump_path(universe, from, is_target, checker, costs, ship, timers),
+    }
+}
+
+/// Finds the cost of the best path between every pair of waypoints.
+///
+/// `legs[a][b]` is the cost from `waypoints[a]` to `waypoints[b]`. Plain routes need one search per waypoint, but the
+/// other modes depend on more than the current system, so each leg is searched on its own.
+fn get_leg_matrix(universe: &Universe, waypoints: &[SystemIndex], checker: &SystemValidityChecker, costs: &CostModel, mode: RouteMode, timers: JumpTimers) -> Vec<Vec<Option<Cost>>> {
+    waypoints.iter()
+        .map(|from| {
+            if !matches!(mode, RouteMode::Jumps) {
+                return waypoints.iter()
+                    .map(|to| get_leg(universe, *from, *to, checker, costs, mode, timers).map(|(cost, _, _)| cost))
+                    .collect();
+            }
+
+            let mut remaining = waypoints.iter().copied().collect::<HashSet<_>>();
+
+            let paths: ShortestPaths<_, _, DenseParents<_>> = search::dijkstra_with(*from, |curr| get_edges(universe, curr, checker, costs), |curr| {
+                remaining.remove(&curr);
+                remaining.is_empty()
+            });
+
+            waypoints.iter()
+                .map(|to| paths.cost(*to))
+                .collect()
+        })
+        .collect()
+}
+
+/// The route between two consecutive waypoints.
+#[derive(Debug, Clone)]
+pub struct Leg {
+    pub start: SystemId,
+    pub end: SystemId,
+    pub cost: Cost,
+    /// None if there is no route from `start` to `end`
+    pub jumps: Option<Path>,
+    /// The jump timers on arrival at `end`
+    pub timers: JumpTimers,
+    /// Set when removed jumps made this leg longer, or impossible
+    pub detour: Option<Detour>,
+}
+
+/// How a leg changed because of removed jumps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detour {
+    /// The removed jumps the leg would have taken, as `(from, to)`
+    pub removed: Vec<(SystemId, SystemId)>,
+    /// None if the leg has no route without them
+    pub extra_cost: Option<Cost>,
+    pub extra_jumps: Option<i64>,
+}
+
+impl Detour {
+    /// Like "2 more jumps (cost +2.00) because Jita -> Perimeter is removed".
+    pub fn describe(&self) -> String {
+        let removed = self.removed.iter()
+            .map(|(from, to)| format!("{} -> {}", get_system_name(*from), get_system_name(*to)))
+            .join(", ");
+
+        let is = if self.removed.len() == 1 { "is" } else { "are" };
+
+        match (self.extra_jumps, self.extra_cost) {
+            (Some(jumps), Some(cost)) => format!("{jumps} more jumps (cost {cost:+.2}) because {removed} {is} removed"),
+            _ => format!("no route because {removed} {is} removed"),
+        }
+    }
+}
+
+/// Compares a leg with the same leg planned without any removed jumps.
+fn get_detour(universe: &Universe, leg: Option<&TimedPath<SystemIndex, Jump>>, unrestricted: Option<&TimedPath<SystemIndex, Jump>>) -> Option<Detour> {
+    let (cost, path, _) = unrestricted?;
+
+    let removed = path.iter()
+        .filter(|(to, from, _)| universe.is_removed(*from, *to))
+        .map(|(to, from, _)| (find_system_by_index(*from).id, find_system_by_index(*to).id))
+        .collect_vec();
+
+    if removed.is_empty() {
+        return None;
+    }
+
+    let extra_cost = leg.map(|(leg_cost, _, _)| leg_cost - cost);
+
+    // another route was just as good
+    if extra_cost.is_some_and(|extra| extra.abs() < 1e-9) {
+        return None;
+    }
+
+    Some(Detour {
+        removed,
+        extra_cost,
+        extra_jumps: leg.map(|(_, leg_path, _)| leg_path.len() as i64 - path.len() as i64),
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct PlannedRoute {
+    /// The waypoints in the order they are visited
+    pub waypoints: Vec<SystemId>,
+    pub legs: Vec<Leg>,
+    pub start_timers: JumpTimers,
+}
+
+impl PlannedRoute {
+    /// Whether every leg has a route.
+    pub fn is_valid(&self) -> bool {
+        self.legs.iter().all(|l| l.jumps.is_some())
+    }
+
+    pub fn get_jump_count(&self) -> usize {
+        self.legs.iter().map(|l| l.jumps.as_ref().map(|v| v.len()).unwrap_or_default()).sum()
+    }
+
+    pub fn get_cost(&self) -> Cost {
+        self.legs.iter().map(|l| l.cost).sum()
+    }
+
+    /// The jump timers at the end of the route.
+    pub fn get_arrival_timers(&self) -> JumpTimers {
+        self.legs.last().map(|l| l.timers).unwrap_or(self.start_timers)
+    }
+}
+
+/// A trip through some waypoints. The first and last waypoints are the start and end, and the ones in between are
+/// visited in the cheapest order unless `exact` is set.
+#[derive(Debug, Clone)]
+pub struct RouteRequest {
+    pub waypoints: Vec<SystemId>,
+    pub filter: SystemValidityChecker,
+    pub costs: CostModel,
+    pub mode: RouteMode,
+    /// The pilot's jump fatigue at the start of the route, in minutes
+    pub fatigue: f64,
+    pub exact: bool,
+}
+
+impl RouteRequest {
+    pub fn new(waypoints: Vec<SystemId>) -> Self {
+        Self {
+            waypoints,
+            filter: SystemValidityChecker::default(),
+            costs: CostModel::default(),
+            mode: RouteMode::Jumps,
+            fatigue: 0.0,
+            exact: false,
+        }
+    }
+
+    pub fn filter(mut self, filter: SystemValidityChecker) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn costs(mut self, costs: CostModel) -> Self {
+        self.costs = costs;
+        self
+    }
+
+    pub fn jump_cost(mut self, via: Jump, cost: Cost) -> Self {
+        self.costs.jump_costs.insert(via, cost);
+        self
+    }
+
+    pub fn preference(mut self, preference: RoutePreference) -> Self {
+        self.costs.preference = preference;
+        self
+    }
+
+    pub fn mode(mut self, mode: RouteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn fatigue(mut self, fatigue: f64) -> Self {
+        self.fatigue = fatigue;
+        self
+    }
+
+    pub fn exact(mut self, exact: bool) -> Self {
+        self.exact = exact;
+        self
+    }
+
+    /// Checks that the request makes sense, without searching for anything.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.waypoints.len() < 2 {
+            bail!("2 or more waypoints must be given");
+        }
+
+        self.validate_options()?;
+
+        if let RouteMode::JumpDrive(_) = self.mode {
+            for sys in self.waypoints.iter().skip(1).map(|id| find_system_by_id(*id)) {
+                if !jump_drive::can_jump_to(sys) {
+                    bail!("cannot jump to {}: cynos can only be lit in lowsec and nullsec", sys.name);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the costs and mode, whatever the waypoints are.
+    fn validate_options(&self) -> anyhow::Result<()> {
+        if self.costs.jump_costs.values().any(|c| !c.is_finite() || *c < 0.0) {
+            bail!("jump costs must be zero or positive");
+        }
+
+        if let RouteMode::Warp(profile) = self.mode {
+            if profile.warp_speed <= 0.0 || profile.align_time < 0.0 || profile.jump_overhead < 0.0 {
+                bail!("the warp speed must be positive, and the align time and jump overhead can't be negative");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the best route from the first waypoint to the nearest system `is_target` accepts, with one search instead
+    /// of one per candidate. A start that matches is its own nearest match, with no jumps. Returns None if no such
+    /// system can be reached. The other waypoints are ignored.
+    pub fn plan_to_nearest(&self, universe: &Universe, is_target: impl Fn(&System) -> bool) -> anyhow::Result<Option<PlannedRoute>> {
+        let Some(from) = self.waypoints.first() else {
+            bail!("a system to start from must be given");
+        };
+
+        self.validate_options()?;
+
+        let start = systems::get_system_index(*from).with_context(|| format!("system {from} doesn't exist"))?;
+        let start_timers = JumpTimers::new(self.fatigue);
+
+        let leg = get_leg_to_any(universe, start, |idx| is_target(find_system_by_index(idx)), &self.filter, &self.costs, self.mode, start_timers);
+
+        let Some((cost, path, timers)) = leg else {
+            return Ok(None);
+        };
+
+        let end = find_system_by_index(path.last().map_or(start, |(to, _, _)| *to)).id;
+
+        Ok(Some(PlannedRoute {
+            waypoints: vec![*from, end],
+            legs: vec![Leg {
+                start: *from,
+                end,
+                cost,
+                jumps: Some(to_path(path)),
+                timers,
+                detour: None,
+            }],
+            start_timers,
+        }))
+    }
+
+    /// Finds the best route through `universe`. Legs that can't be routed are kept, without any jumps.
+    pub fn plan(&self, universe: &Universe) -> anyhow::Result<PlannedRoute> {
+        self.validate()?;
+
+        let start_timers = JumpTimers::new(self.fatigue);
+
+        let indices = self.waypoints.iter()
+            .map(|id| systems::get_system_index(*id).with_context(|| format!("system {id} doesn't exist")))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let waypoints = if self.exact {
+            indices
+        } else {
+            let legs = get_leg_matrix(universe, &indices, &self.filter, &self.costs, self.mode, start_timers);
+
+            let costs = legs.iter()
+                .map(|row| row.iter().map(|cost| cost.unwrap_or(UNREACHABLE_LEG_COST)).collect())
+                .collect_vec();
+
+            crate::tour::best_order(&costs).into_iter().map(|i| indices[i]).collect_vec()
+        };
+
+        // only needed to explain detours
+        let unrestricted = universe.has_removed_jumps().then(|| universe.without_removals());
+
+        // jump timers carry over from one leg to the next
+        let mut timers = start_timers;
+
+        let legs = waypoints.iter()
+            .tuple_windows()
+            .map(|(start, end)| {
+                let leg = get_leg(universe, *start, *end, &self.filter, &self.costs, self.mode, timers);
+
+                let detour = unrestricted.as_ref().and_then(|all| {
+                    let best = get_leg(all, *start, *end, &self.filter, &self.costs, self.mode, timers);
+
+                    get_detour(universe, leg.as_ref(), best.as_ref())
+                });
+
+                if let Some((_, _, arrival)) = &leg {
+                    timers = *arrival;
+                }
+
+                Leg {
+                    start: find_system_by_index(*start).id,
+                    end: find_system_by_index(*end).id,
+                    cost: leg.as_ref().map(|(cost, _, _)| *cost).unwrap_or_default(),
+                    jumps: leg.map(|(_, path, _)| to_path(path)),
+                    timers,
+                    detour,
+                }
+            })
+            .collect_vec();
+
+        Ok(PlannedRoute {
+            waypoints: waypoints.into_iter().map(|idx| find_system_by_index(idx).id).collect(),
+            legs,
+            start_timers,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::test_system, ShipSize, WormholeFilter, WormholeInfo};
+
+    /// The 12 jump route from Jita to Dodixie, which cuts through lowsec in The Citadel.
+    const JITA_DODIXIE_SHORTEST: &[(&str, f32)] = &[
+        ("Jita", 0.95), ("Ikuchi", 0.99), ("Tunttaras", 0.89), ("Nourvukaiken", 0.82), ("Tama", 0.28),
+        ("Sujarento", 0.31), ("Onatoh", 0.30), ("Tannolen", 0.30), ("Tierijev", 0.84), ("Chantrousse", 0.64),
+        ("Ourapheh", 0.86), ("Botane", 0.88), ("Dodixie", 0.87),
+    ];
+
+    /// The 15 jump highsec-only route from Jita to Dodixie, through Uedama.
+    const JITA_DODIXIE_SAFEST: &[(&str, f32)] = &[
+        ("Jita", 0.95), ("Perimeter", 0.95), ("Urlen", 0.96), ("Kusomonmon", 0.85), ("Suroken", 0.73),
+        ("Haatomo", 0.61), ("Uedama", 0.51), ("Sivala", 0.55), ("Hatakani", 0.94), ("Kassigainen", 0.92),
+        ("Algogille", 0.93), ("Renyn", 0.90), ("Grinacanne", 0.85), ("Erme", 0.80), ("Botane", 0.88),
+        ("Dodixie", 0.87),
+    ];
+
+    /// A hand-built gate network, so the preference tests don't depend on the SDE the crate was built with.
+    struct TestMap {
+        systems: HashMap<&'static str, System>,
+        jumps: HashMap<SystemId, HashSet<(SystemId, Jump)>>,
+    }
+
+    impl TestMap {
+        /// Builds a gate network where each route is a chain of two-way gates.
+        fn new(routes: &[&[(&'static str, f32)]]) -> Self {
+            let mut systems = HashMap::<&'static str, System>::new();
+            let mut jumps = HashMap::<SystemId, HashSet<(SystemId, Jump)>>::new();
+
+            for route in routes {
+                for (name, security) in route.iter() {
+                    let id = systems.len() as SystemId + 1;
+
+                    systems.entry(name).or_insert(System { id, ..test_system(name, *security) });
+                }
+
+                for ((a, _), (b, _)) in route.iter().tuple_windows() {
+                    let (a, b) = (systems[a].id, systems[b].id);
+
+                    jumps.entry(a).or_default().insert((b, Jump::Gate));
+                    jumps.entry(b).or_default().insert((a, Jump::Gate));
+                }
+            }
+
+            Self { systems, jumps }
+        }
+
+        fn route(&self, from: &str, to: &str, preference: RoutePreference) -> Vec<&'static str> {
+            let by_id = self.systems.values().map(|s| (s.id, s)).collect::<HashMap<_, _>>();
+
+            let costs = CostModel::new(&[], preference);
+            let checker = SystemValidityChecker::default();
+
+            let (_, path) = search::shortest_path(self.systems[from].id, self.systems[to].id, |curr| {
+                self.jumps[&curr]
+                    .iter()
+                    .map(|(n, via)| (*n, *via, costs.get_jump_cost(by_id[n], *via, &checker)))
+                    .collect_vec()
+            }).unwrap();
+
+            std::iter::once(self.systems[from].name)
+                .chain(path.into_iter().map(|(to, _, _)| by_id[&to].name))
+                .collect()
+        }
+    }
+
+    fn names(route: &[(&'static str, f32)]) -> Vec<&'static str> {
+        route.iter().map(|(name, _)| *name).collect()
+    }
+
+    #[test]
+    fn shorter_takes_the_lowsec_shortcut() {
+        let map = TestMap::new(&[JITA_DODIXIE_SHORTEST, JITA_DODIXIE_SAFEST]);
+
+        assert_eq!(map.route("Jita", "Dodixie", RoutePreference::Shorter), names(JITA_DODIXIE_SHORTEST));
+    }
+
+    #[test]
+    fn safer_stays_in_highsec() {
+        let map = TestMap::new(&[JITA_DODIXIE_SHORTEST, JITA_DODIXIE_SAFEST]);
+
+        assert_eq!(map.route("Jita", "Dodixie", RoutePreference::Safer), names(JITA_DODIXIE_SAFEST));
+        assert_eq!(map.route("Dodixie", "Jita", RoutePreference::Safer), names(JITA_DODIXIE_SAFEST).into_iter().rev().collect_vec());
+    }
+
+    #[test]
+    fn less_secure_avoids_highsec() {
+        // Urlen is two highsec jumps from Jita, or three through lowsec in The Citadel
+        let map = TestMap::new(&[
+            &[("Jita", 0.95), ("Perimeter", 0.95), ("Urlen", 0.96)],
+            &[("Jita", 0.95), ("Tama", 0.30), ("Sujarento", 0.31), ("Urlen", 0.96)],
+        ]);
+
+        assert_eq!(map.route("Jita", "Urlen", RoutePreference::Shorter), vec!["Jita", "Perimeter", "Urlen"]);
+        assert_eq!(map.route("Jita", "Urlen", RoutePreference::LessSecure), vec!["Jita", "Tama", "Sujarento", "Urlen"]);
+    }
+
+    #[test]
+    fn safer_prefers_higher_lowsec() {
+        // both routes have to cross lowsec, but Enaluri (0.43) is far safer than Aeschee (0.07)
+        let map = TestMap::new(&[
+            &[("Jita", 0.95), ("Aeschee", 0.07), ("Dodixie", 0.87)],
+            &[("Jita", 0.95), ("Perimeter", 0.95), ("Enaluri", 0.43), ("Urlen", 0.96), ("Dodixie", 0.87)],
+        ]);
+
+        assert_eq!(map.route("Jita", "Dodixie", RoutePreference::Shorter), vec!["Jita", "Aeschee", "Dodixie"]);
+        assert_eq!(map.route("Jita", "Dodixie", RoutePreference::Safer), vec!["Jita", "Perimeter", "Enaluri", "Urlen", "Dodixie"]);
+    }
+
+    #[test]
+    fn timed_penalties_dont_scale_with_time() {
+        // a 10 second hop into 0.3 lowsec against a 15 minute hop through highsec, with --prefer safer
+        let lowsec = SECURITY_PREFERENCE_PENALTY * 0.7;
+
+        assert!(get_timed_cost(1.0, lowsec, 10.0) > get_timed_cost(1.0, 0.0, 900.0));
+        assert!(get_timed_cost(1.0, FILTERED_JUMP_PENALTY, 1.0) > get_timed_cost(1.0, 0.0, 3600.0));
+
+        // the jump's own cost still scales its time
+        assert_eq!(get_timed_cost(2.0, 0.0, 30.0), get_timed_cost(1.0, 0.0, 60.0));
+    }
+
+    #[test]
+    fn builder_overrides_defaults() {
+        let request = RouteRequest::new(vec![1, 2])
+            .jump_cost(Jump::Wormhole, 3.0)
+            .preference(RoutePreference::Safer)
+            .fatigue(30.0)
+            .exact(true);
+
+        assert_eq!(request.costs.jump_costs[&Jump::Wormhole], 3.0);
+        assert_eq!(request.costs.jump_costs[&Jump::Gate], 1.0);
+        assert_eq!(request.costs.preference, RoutePreference::Safer);
+        assert_eq!(request.fatigue, 30.0);
+        assert!(request.exact);
+        assert!(request.filter.strict);
+    }
+
+    #[test]
+    fn removed_jumps_show_up_as_detours() {
+        let jita = crate::find_system_by_name("Jita").unwrap();
+        let perimeter = crate::find_system_by_name("Perimeter").unwrap();
+
+        let mut universe = Universe::stargates();
+
+        let direct = RouteRequest::new(vec![jita, perimeter]).plan(&universe).unwrap();
+
+        assert_eq!(direct.get_jump_count(), 1);
+        assert_eq!(direct.legs[0].detour, None);
+
+        universe.remove_jump(jita, perimeter);
+
+        let route = RouteRequest::new(vec![jita, perimeter]).plan(&universe).unwrap();
+        let detour = route.legs[0].detour.as_ref().unwrap();
+
+        assert_eq!(detour.removed, vec![(jita, perimeter)]);
+        assert_eq!(detour.extra_jumps, Some(route.get_jump_count() as i64 - 1));
+
+        // the other direction still works
+        assert_eq!(RouteRequest::new(vec![perimeter, jita]).plan(&universe).unwrap().legs[0].detour, None);
+    }
+
+    #[test]
+    fn ships_skip_holes_they_dont_fit_through() {
+        let jita = crate::find_system_by_name("Jita").unwrap();
+        let thera = crate::find_system_by_name("Thera").unwrap();
+
+        let mut universe = Universe::stargates();
+
+        universe.add_wormhole(jita, thera, WormholeInfo { size: Some(ShipSize::Medium), ..Default::default() });
+
+        let route = |ship_size| {
+            let filter = SystemValidityChecker {
+                wormholes: WormholeFilter { ship_size, ..Default::default() },
+                ..Default::default()
+            };
+
+            RouteRequest::new(vec![jita, thera]).filter(filter).plan(&universe).unwrap()
+        };
+
+        assert_eq!(route(None).get_jump_count(), 1);
+        assert_eq!(route(Some(ShipSize::Medium)).get_jump_count(), 1);
+        assert!(!route(Some(ShipSize::Large)).is_valid());
+    }
+
+    #[test]
+    fn connections_stop_working_when_they_expire() {
+        let jita = crate::find_system_by_name("Jita").unwrap();
+        let thera = crate::find_system_by_name("Thera").unwrap();
+
+        let at = |time| humantime::parse_rfc3339(time).unwrap();
+
+        let mut universe = Universe::stargates();
+
+        universe.load_connections(
+            "links.toml",
+            "[[connection]]\nfrom = \"Jita\"\nto = \"Thera\"\nexpires = \"2024-05-01T18:00:00Z\"\n",
+            crate::connections::ConnectionFormat::Toml,
+            crate::diagnostics::ParseMode::Strict,
+            at("2024-05-01T12:00:00Z"),
+        ).unwrap();
+
+        let route = |now| {
+            let filter = SystemValidityChecker { wormholes: WormholeFilter { now, ..Default::default() }, ..Default::default() };
+
+            RouteRequest::new(vec![jita, thera]).filter(filter).plan(&universe).unwrap()
+        };
+
+        assert_eq!(route(at("2024-05-01T13:00:00Z")).get_jump_count(), 1);
+        assert!(!route(at("2024-05-01T19:00:00Z")).is_valid());
+    }
+
+    #[test]
+    fn nearby_systems_are_counted_in_jumps() {
+        let jita = crate::find_system_by_name("Jita").unwrap();
+        let perimeter = crate::find_system_by_name("Perimeter").unwrap();
+        let thera = crate::find_system_by_name("Thera").unwrap();
+
+        let mut universe = Universe::stargates();
+
+        universe.add_wormhole(jita, thera, WormholeInfo::default());
+
+        let nearby = find_nearby(&universe, jita, 2, &SystemValidityChecker::default()).unwrap();
+
+        let get = |id| nearby.iter().find(|sys| sys.id == id).unwrap();
+
+        assert_eq!((get(perimeter).jumps, get(perimeter).via.clone()), (1, vec![Jump::Gate]));
+        assert_eq!((get(thera).jumps, get(thera).via.clone()), (1, vec![Jump::Wormhole]));
+        assert!(nearby.iter().all(|sys| sys.id != jita && (1..=2).contains(&sys.jumps)));
+        assert!(nearby.is_sorted_by_key(|sys| sys.jumps));
+
+        let highsec = SystemValidityChecker { invalid_securities: vec![SystemSecurity::Special], ..Default::default() };
+
+        assert!(!find_nearby(&universe, jita, 2, &highsec).unwrap().iter().any(|sys| sys.id == thera));
+    }
+
+    #[test]
+    fn nearest_match_takes_one_search() {
+        let jita = crate::find_system_by_name("Jita").unwrap();
+
+        let request = RouteRequest::new(vec![jita]);
+        let universe = Universe::stargates();
+
+        // one search asks about each system at most once, while one per candidate would ask again for each
+        let asked = std::cell::RefCell::new(HashSet::new());
+
+        let lowsec = request.plan_to_nearest(&universe, |sys| {
+            assert!(asked.borrow_mut().insert(sys.id), "{} was asked about twice", sys.name);
+
+            sys.get_security_class() == SystemSecurity::Lowsec
+        }).unwrap().unwrap();
+        let end = lowsec.waypoints[1];
+
+        assert_eq!(find_system_by_id(end).get_security_class(), SystemSecurity::Lowsec);
+        assert_eq!(lowsec.get_jump_count(), RouteRequest::new(vec![jita, end]).plan(&universe).unwrap().get_jump_count());
+        assert_eq!(lowsec.legs[0].jumps.as_ref().unwrap().last().unwrap().0, end);
+
+        // a start that matches is its own nearest match
+        let here = request.plan_to_nearest(&universe, |sys| sys.id == jita).unwrap().unwrap();
+
+        assert_eq!(here.waypoints, vec![jita, jita]);
+        assert_eq!(here.get_jump_count(), 0);
+        assert!(RouteRequest::new(Vec::new()).plan_to_nearest(&universe, |_| true).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        assert!(RouteRequest::new(vec![1]).validate().is_err());
+        assert!(RouteRequest::new(vec![1, 2]).jump_cost(Jump::Gate, -1.0).validate().is_err());
+
+        let slow = WarpProfile { warp_speed: 0.0, align_time: 10.0, jump_overhead: 10.0 };
+
+        assert!(RouteRequest::new(vec![1, 2]).mode(RouteMode::Warp(slow)).validate().is_err());
+    }
+}
@@ -0,0 +1,714 @@
+//! Parsing for the SDE zip.
+//!
+//! This file is shared between the build script (which bakes the universe into the binary) and the library (which can
+//! load a newer SDE at runtime), so it must not use anything from the crate itself.
+//!
+//! Two layouts are supported:
+//! - The legacy layout: a YAML file per region, constellation, and system under `sde/fsd/universe`, with names in
+//!   `sde/bsd/invUniqueNames.yaml`.
+//! - The flat layout: one `mapSolarSystems`, `mapStargates`, `mapRegions`, and `mapConstellations` file each, as
+//!   either JSONL or YAML, with names inline.
+
+use std::{path::{Path, PathBuf}, collections::{BTreeSet, HashMap}, fmt, io::Read};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use rayon::prelude::*;
+use itertools::Itertools;
+
+type GateId = i64;
+type SystemId = i64;
+type UniqueNames = HashMap<i64, (i64, String)>;
+
+#[derive(Debug, Clone, Deserialize)]
+struct SDEGate {
+    pub destination: GateId,
+    pub position: [f64; 3],
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SDESystem {
+    pub center: [f64; 3],
+    pub security: f32,
+    #[serde(rename = "solarSystemID")]
+    pub solar_system_id: i64,
+    pub stargates: HashMap<GateId, SDEGate>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SDEConstellation {
+    #[serde(rename = "constellationID")]
+    pub constellation_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SDERegion {
+    #[serde(rename = "regionID")]
+    pub region_id: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SDEPosition {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl SDEPosition {
+    fn to_array(&self) -> [f64; 3] {
+        [self.x, self.y, self.z]
+    }
+}
+
+/// Names are localized in the flat layout, but older exports of it only have the english name.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum SDEName {
+    Localized { en: String },
+    Plain(String),
+}
+
+impl SDEName {
+    fn get_english(&self) -> &str {
+        match self {
+            SDEName::Localized { en } => en,
+            SDEName::Plain(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SDEMapSolarSystem {
+    pub name: SDEName,
+    #[serde(rename = "constellationID")]
+    pub constellation_id: i64,
+    #[serde(rename = "regionID")]
+    pub region_id: i64,
+    pub position: SDEPosition,
+    pub security_status: f32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SDEMapStargateDestination {
+    #[serde(rename = "stargateID")]
+    pub stargate_id: GateId,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SDEMapStargate {
+    #[serde(rename = "solarSystemID")]
+    pub solar_system_id: SystemId,
+    pub destination: SDEMapStargateDestination,
+    pub position: SDEPosition,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SDEMapNamed {
+    pub name: SDEName,
+}
+
+/// A JSONL row, which carries its own ID.
+#[derive(Debug, Clone, Deserialize)]
+struct SDERow<T> {
+    #[serde(rename = "_key")]
+    pub key: i64,
+    #[serde(flatten)]
+    pub value: T,
+}
+
+/// A solar system, with everything the planner needs from the SDE.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemRecord {
+    pub name: String,
+    pub security: f32,
+    pub id: SystemId,
+    pub const_id: i64,
+    pub constellation: String,
+    pub region_id: i64,
+    pub region: String,
+    pub position: [f64; 3],
+    pub jumps: Vec<SystemId>,
+    /// `(destination, position)`, sorted by destination
+    pub gates: Vec<(SystemId, [f64; 3])>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UniverseRecords {
+    /// Sorted by ID
+    pub systems: Vec<SystemRecord>,
+    /// `(name, id)`, sorted by name
+    pub regions: Vec<(String, i64)>,
+    /// The gate network as a compressed sparse row graph over positions in `systems`: the gates out of `systems[i]`
+    /// lead to `gate_targets[gate_offsets[i]..gate_offsets[i + 1]]`.
+    pub gate_offsets: Vec<u32>,
+    pub gate_targets: Vec<u32>,
+}
+
+/// Problems that make an SDE unusable, found while validating it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdeErrors(pub Vec<String>);
+
+impl fmt::Display for SdeErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the SDE failed validation with {} error{}:", self.0.len(), if self.0.len() == 1 { "" } else { "s" })?;
+
+        for err in &self.0 {
+            write!(f, "\n  - {err}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for SdeErrors {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SdeLayout {
+    /// `sde/fsd/universe/...` and `sde/bsd/invUniqueNames.yaml`
+    Legacy,
+    /// `mapSolarSystems.jsonl` and friends
+    Jsonl,
+    /// `mapSolarSystems.yaml` and friends
+    Yaml,
+}
+
+const FLAT_TABLES: [&str; 4] = ["mapSolarSystems", "mapStargates", "mapRegions", "mapConstellations"];
+
+/// The name of a flat layout table, if this is one.
+fn get_flat_table(path: &Path) -> Option<(&'static str, SdeLayout)> {
+    let stem = path.file_stem()?.to_str()?;
+    let ext = path.extension()?.to_str()?;
+
+    let layout = match ext {
+        "jsonl" => SdeLayout::Jsonl,
+        "yaml" => SdeLayout::Yaml,
+        _ => return None,
+    };
+
+    FLAT_TABLES.into_iter().find(|t| *t == stem).map(|t| (t, layout))
+}
+
+fn is_legacy_file(path: &Path) -> bool {
+    path.starts_with("sde/fsd/universe/eve") || path.starts_with("sde/fsd/universe/wormhole") || path == Path::new("sde/bsd/invUniqueNames.yaml")
+}
+
+/// Works out which layout an SDE uses from its file names. The flat layout wins if both are present, since it's newer.
+pub fn detect_layout<'a>(paths: impl IntoIterator<Item = &'a Path>) -> Result<SdeLayout> {
+    let mut legacy = false;
+    let mut flat = None;
+
+    for path in paths {
+        if let Some(("mapSolarSystems", layout)) = get_flat_table(path) {
+            flat = Some(layout);
+        }
+
+        if path.starts_with("sde/fsd/universe") {
+            legacy = true;
+        }
+    }
+
+    match (flat, legacy) {
+        (Some(layout), _) => Ok(layout),
+        (None, true) => Ok(SdeLayout::Legacy),
+        (None, false) => bail!("unrecognized SDE layout: expected either mapSolarSystems.jsonl/.yaml or sde/fsd/universe"),
+    }
+}
+
+/// Parses the solar systems and regions out of an SDE zip. Also returns warnings for problems that could be worked
+/// around; anything worse fails with [`SdeErrors`].
+pub fn load_universe(sde: &Path) -> Result<(UniverseRecords, Vec<String>)> {
+    let mut sde = zip::read::ZipArchive::new({
+        std::fs::OpenOptions::new()
+            .read(true)
+            .open(sde)
+            .with_context(|| format!("could not open {}", sde.display()))?
+    })?;
+
+    let paths = (0..sde.len())
+        .filter_map(|idx| sde.by_index(idx).ok()?.enclosed_name().map(|p| p.to_owned()))
+        .collect::<Vec<_>>();
+
+    let layout = detect_layout(paths.iter().map(|p| p.as_path()))?;
+
+    let mut files = Vec::new();
+
+    for idx in 0..sde.len() {
+        let mut file = sde.by_index(idx)?;
+        let path = match file.enclosed_name() {
+            Some(p) => p.to_owned(),
+            None => {
+                continue;
+            }
+        };
+
+        let wanted = match layout {
+            SdeLayout::Legacy => is_legacy_file(&path),
+            SdeLayout::Jsonl | SdeLayout::Yaml => get_flat_table(&path).is_some_and(|(_, l)| l == layout),
+        };
+
+        if wanted {
+            let mut buf = String::new();
+            file.read_to_string(&mut buf)?;
+
+            files.push((path, buf));
+        }
+    }
+
+    parse_files(&files)
+}
+
+/// Parses the solar systems and regions out of the files in an SDE, as `(path in the zip, contents)`. Returns the same
+/// warnings as [`load_universe`].
+pub fn parse_files(files: &[(PathBuf, String)]) -> Result<(UniverseRecords, Vec<String>)> {
+    let raw = match detect_layout(files.iter().map(|(p, _)| p.as_path()))? {
+        SdeLayout::Legacy => parse_legacy(files)?,
+        layout => parse_flat(files, layout)?,
+    };
+
+    link_universe(raw)
+}
+
+/// Everything both layouts have in common, before the gates are resolved to systems.
+#[derive(Debug, Clone, Default)]
+struct RawUniverse {
+    /// Without any jumps or gates yet, and with empty names where the SDE didn't have one
+    pub systems: HashMap<SystemId, SystemRecord>,
+    /// gate -> (destination gate, system, position)
+    pub gates: HashMap<GateId, (GateId, SystemId, [f64; 3])>,
+    pub regions: Vec<(String, i64)>,
+}
+
+/// Resolves gates to the systems they lead to, and checks that the result makes sense.
+///
+/// Gates to missing gates are skipped, and regions or constellations without a name are named after their ID, with a
+/// warning for each. Systems without a name, duplicate system names, and gates in missing systems are errors. Gates
+/// without one leading back are allowed, but warned about.
+fn link_universe(raw: RawUniverse) -> Result<(UniverseRecords, Vec<String>)> {
+    let RawUniverse { mut systems, gates, mut regions } = raw;
+
+    let mut errors = BTreeSet::new();
+    let mut warnings = BTreeSet::new();
+
+    for sys in systems.values_mut() {
+        if sys.name.is_empty() {
+            errors.insert(format!("system {} has no name", sys.id));
+        }
+
+        if sys.constellation.is_empty() {
+            sys.constellation = format!("constellation {}", sys.const_id);
+
+            warnings.insert(format!("constellation {} has no name, calling it '{}'", sys.const_id, sys.constellation));
+        }
+
+        if sys.region.is_empty() {
+            sys.region = format!("region {}", sys.region_id);
+
+            warnings.insert(format!("region {} has no name, calling it '{}'", sys.region_id, sys.region));
+            regions.push((sys.region.clone(), sys.region_id));
+        }
+    }
+
+    for (name, ids) in systems.values().filter(|s| !s.name.is_empty()).into_group_map_by(|s| s.name.clone()) {
+        if ids.len() > 1 {
+            errors.insert(format!("{} systems are named '{name}': {}", ids.len(), ids.iter().map(|s| s.id).sorted().join(", ")));
+        }
+    }
+
+    for (gid, (dst_gate, from, position)) in &gates {
+        let Some((_, to, _)) = gates.get(dst_gate) else {
+            warnings.insert(format!("stargate {gid} in system {from} leads to stargate {dst_gate}, which doesn't exist; skipping it"));
+            continue;
+        };
+
+        let Some(from) = systems.get_mut(from) else {
+            errors.insert(format!("stargate {gid} is in system {from}, which doesn't exist"));
+            continue;
+        };
+
+        from.jumps.push(*to);
+        from.gates.push((*to, *position));
+    }
+
+    if !errors.is_empty() {
+        return Err(SdeErrors(errors.into_iter().collect()).into());
+    }
+
+    for sys in systems.values() {
+        for to in &sys.jumps {
+            if !systems[to].jumps.contains(&sys.id) {
+                warnings.insert(format!("one-way gate from {} ({}) to {} ({})", sys.name, sys.id, systems[to].name, to));
+            }
+        }
+    }
+
+    let mut systems = systems.into_values().collect::<Vec<_>>();
+
+    systems.sort_by_key(|s| s.id);
+
+    for sys in &mut systems {
+        sys.jumps.sort();
+        sys.gates.sort_by_key(|(destination, _)| *destination);
+    }
+
+    regions.sort();
+    regions.dedup();
+
+    let index = systems.iter().enumerate().map(|(idx, s)| (s.id, idx as u32)).collect::<HashMap<_, _>>();
+
+    let mut gate_offsets = vec![0];
+    let mut gate_targets = Vec::new();
+
+    for sys in &systems {
+        gate_targets.extend(sys.jumps.iter().map(|to| index[to]));
+        gate_offsets.push(gate_targets.len() as u32);
+    }
+
+    Ok((UniverseRecords { systems, regions, gate_offsets, gate_targets }, warnings.into_iter().collect()))
+}
+
+fn parse_legacy(files: &[(PathBuf, String)]) -> Result<RawUniverse> {
+    let names = files.iter()
+        .find(|(path, _)| path == Path::new("sde/bsd/invUniqueNames.yaml"))
+        .context("missing sde/bsd/invUniqueNames.yaml")?;
+
+    let (names, parsed) = rayon::join(
+        || load_names(&names.1),
+        || files.par_iter().map(parse).collect::<Result<Vec<_>>>(),
+    );
+
+    let names = names?;
+    let parsed = parsed?;
+
+    let mut sde_regions_by_name = HashMap::<String, i64>::new();
+
+    let mut sde_constellations_by_name = HashMap::<String, i64>::new();
+
+    for file in parsed.iter().flatten() {
+        match file {
+            ParsedFile::Region(reg_name, reg) => {
+                sde_regions_by_name.insert(reg_name.clone(), reg.region_id);
+            },
+            ParsedFile::Constellation(con_name, con) => {
+                sde_constellations_by_name.insert(con_name.clone(), con.constellation_id);
+            },
+            _ => {}
+        }
+    }
+
+    // missing names are reported by link_universe
+    let get_name = |id: i64| names.get(&id).map(|(_, name)| name.clone()).unwrap_or_default();
+
+    let mut raw = RawUniverse::default();
+
+    for file in parsed.iter().flatten() {
+        if let ParsedFile::System(reg_name, con_name, sys) = file {
+            let reg_id = *sde_regions_by_name.get(reg_name).with_context(|| format!("no region.staticdata for region {reg_name}"))?;
+            let con_id = *sde_constellations_by_name.get(con_name).with_context(|| format!("no constellation.staticdata for constellation {con_name}"))?;
+
+            raw.systems.insert(sys.solar_system_id, SystemRecord {
+                name: get_name(sys.solar_system_id),
+                security: sys.security,
+                id: sys.solar_system_id,
+                const_id: con_id,
+                constellation: get_name(con_id),
+                region_id: reg_id,
+                region: get_name(reg_id),
+                position: sys.center,
+                jumps: Vec::new(),
+                gates: Vec::new(),
+            });
+
+            for (gid, gate) in sys.stargates.iter() {
+                raw.gates.insert(*gid, (gate.destination, sys.solar_system_id, gate.position));
+            }
+        }
+    }
+
+    raw.regions = names.into_iter()
+        .filter(|(_, (group, _))| *group == 3)
+        .map(|(id, (_, name))| (name, id))
+        .collect();
+
+    Ok(raw)
+}
+
+/// Parses one table of the flat layout into `(id, row)`.
+fn parse_table<T: DeserializeOwned>(path: &Path, contents: &str, layout: SdeLayout) -> Result<Vec<(i64, T)>> {
+    match layout {
+        SdeLayout::Jsonl => contents.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(idx, line)| {
+                let row = serde_json::from_str::<SDERow<T>>(line).with_context(|| format!("could not parse {}, line {}", path.display(), idx + 1))?;
+
+                Ok((row.key, row.value))
+            })
+            .collect(),
+        _ => {
+            let rows = serde_yaml::from_str::<HashMap<i64, T>>(contents).with_context(|| format!("could not parse {}", path.display()))?;
+
+            Ok(rows.into_iter().collect())
+        },
+    }
+}
+
+fn parse_flat(files: &[(PathBuf, String)], layout: SdeLayout) -> Result<RawUniverse> {
+    let get_table = |table: &str| {
+        files.iter()
+            .find(|(path, _)| get_flat_table(path) == Some((table, layout)))
+            .ok_or_else(|| anyhow!("missing {table} in the SDE"))
+    };
+
+    let (path, contents) = get_table("mapRegions")?;
+    let region_names = parse_table::<SDEMapNamed>(path, contents, layout)?
+        .into_iter()
+        .map(|(id, r)| (id, r.name.get_english().to_owned()))
+        .collect::<HashMap<_, _>>();
+
+    let (path, contents) = get_table("mapConstellations")?;
+    let constellation_names = parse_table::<SDEMapNamed>(path, contents, layout)?
+        .into_iter()
+        .map(|(id, c)| (id, c.name.get_english().to_owned()))
+        .collect::<HashMap<_, _>>();
+
+    let mut raw = RawUniverse::default();
+
+    let (path, contents) = get_table("mapSolarSystems")?;
+
+    for (id, sys) in parse_table::<SDEMapSolarSystem>(path, contents, layout)? {
+        raw.systems.insert(id, SystemRecord {
+            name: sys.name.get_english().to_owned(),
+            security: sys.security_status,
+            id,
+            const_id: sys.constellation_id,
+            constellation: constellation_names.get(&sys.constellation_id).cloned().unwrap_or_default(),
+            region_id: sys.region_id,
+            region: region_names.get(&sys.region_id).cloned().unwrap_or_default(),
+            position: sys.position.to_array(),
+            jumps: Vec::new(),
+            gates: Vec::new(),
+        });
+    }
+
+    let (path, contents) = get_table("mapStargates")?;
+
+    for (id, gate) in parse_table::<SDEMapStargate>(path, contents, layout)? {
+        raw.gates.insert(id, (gate.destination.stargate_id, gate.solar_system_id, gate.position.to_array()));
+    }
+
+    raw.regions = region_names.into_iter().map(|(id, name)| (name, id)).collect();
+
+    Ok(raw)
+}
+
+fn load_names(unique_names: &str) -> Result<UniqueNames> {
+    let mut out = HashMap::new();
+
+    let mut group_id: Option<i64> = None;
+    let mut item_id: Option<i64> = None;
+    let mut item_name: Option<String> = None;
+
+    for mut line in unique_names.lines() {
+        // each entry starts with a '-', which ends the previous one
+        if let Some(rest) = line.strip_prefix('-') {
+            if let (Some(group), Some(id), Some(name)) = (group_id.take(), item_id.take(), item_name.take()) {
+                if matches!(group, 3..=5) {
+                    out.insert(id, (group, name));
+                }
+            }
+
+            line = rest;
+        }
+
+        line = line.trim();
+
+        if line.starts_with("groupID") {
+            line = line.trim_start_matches("groupID:").trim();
+
+            group_id = Some(line.parse().with_context(|| format!("invalid groupID '{line}'"))?);
+        } else if line.starts_with("itemID") {
+            line = line.trim_start_matches("itemID:").trim();
+
+            item_id = Some(line.parse().with_context(|| format!("invalid itemID '{line}'"))?);
+        } else if line.starts_with("itemName") {
+            line = line.trim_start_matches("itemName:").trim();
+
+            item_name = Some(line.to_owned());
+        }
+    }
+
+    if let (Some(group), Some(id), Some(name)) = (group_id, item_id, item_name) {
+        if matches!(group, 3..=5) {
+            out.insert(id, (group, name));
+        }
+    }
+
+    Ok(out)
+}
+
+enum ParsedFile {
+    Region(String, SDERegion),
+    Constellation(String, SDEConstellation),
+    System(String, String, SDESystem),
+}
+
+/// The name of the directory `up` levels above the file.
+fn get_parent_name(path: &Path, up: usize) -> Result<String> {
+    let mut components = path.components();
+
+    for _ in 0..up {
+        components.next_back();
+    }
+
+    let name = components.next_back().with_context(|| format!("{} is not nested deep enough", path.display()))?;
+
+    Ok(name.as_os_str().to_string_lossy().to_string())
+}
+
+fn parse((path, contents): &(PathBuf, String)) -> Result<Option<ParsedFile>> {
+    let file_name = path.file_name().unwrap_or_default();
+
+    let parsed = if file_name == "region.staticdata" {
+        let reg = serde_yaml::from_str::<SDERegion>(contents).with_context(|| format!("could not parse {}", path.display()))?;
+
+        ParsedFile::Region(get_parent_name(path, 1)?, reg)
+    } else if file_name == "constellation.staticdata" {
+        let con = serde_yaml::from_str::<SDEConstellation>(contents).with_context(|| format!("could not parse {}", path.display()))?;
+
+        ParsedFile::Constellation(get_parent_name(path, 1)?, con)
+    } else if file_name == "solarsystem.staticdata" {
+        let sys = serde_yaml::from_str::<SDESystem>(contents).with_context(|| format!("could not parse {}", path.display()))?;
+
+        ParsedFile::System(get_parent_name(path, 3)?, get_parent_name(path, 2)?, sys)
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(parsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads every file under a fixture directory, with paths relative to it like they would be in the zip.
+    fn read_fixture(name: &str) -> Vec<(PathBuf, String)> {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+
+        let mut files = Vec::new();
+        let mut pending = vec![root.clone()];
+
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+
+                if path.is_dir() {
+                    pending.push(path);
+                } else {
+                    let contents = std::fs::read_to_string(&path).unwrap();
+
+                    files.push((path.strip_prefix(&root).unwrap().to_owned(), contents));
+                }
+            }
+        }
+
+        files
+    }
+
+    fn jumps(records: &UniverseRecords) -> Vec<(&str, Vec<SystemId>)> {
+        records.systems.iter().map(|s| (s.name.as_str(), s.jumps.clone())).collect()
+    }
+
+    #[test]
+    fn detects_layouts() {
+        let layout = |paths: &[&str]| detect_layout(paths.iter().map(Path::new));
+
+        assert_eq!(layout(&["sde/fsd/universe/eve/Derelik/region.staticdata"]).unwrap(), SdeLayout::Legacy);
+        assert_eq!(layout(&["mapSolarSystems.jsonl", "mapStargates.jsonl"]).unwrap(), SdeLayout::Jsonl);
+        assert_eq!(layout(&["sde/mapSolarSystems.yaml"]).unwrap(), SdeLayout::Yaml);
+        assert!(layout(&["types.jsonl"]).is_err());
+    }
+
+    #[test]
+    fn parses_the_legacy_layout() {
+        let (records, warnings) = parse_files(&read_fixture("sde-legacy")).unwrap();
+
+        assert!(warnings.is_empty());
+
+        assert_eq!(jumps(&records), vec![
+            ("Tanoo", vec![30000002]),
+            ("Lashesih", vec![30000001, 30000142]),
+            ("Jita", vec![30000002]),
+        ]);
+
+        assert_eq!(records.systems[0].region, "Derelik");
+        assert_eq!(records.systems[0].constellation, "San Matar");
+        assert_eq!(records.systems[2].region, "The Forge");
+        assert_eq!(records.systems[1].gates[1], (30000142, [7.0, 8.0, 9.0]));
+        assert_eq!(records.regions, vec![("Derelik".to_owned(), 10000001), ("The Forge".to_owned(), 10000002)]);
+        assert_eq!(records.gate_offsets, vec![0, 1, 3, 4]);
+        assert_eq!(records.gate_targets, vec![1, 0, 2, 1]);
+    }
+
+    #[test]
+    fn every_layout_gives_the_same_universe() {
+        let legacy = parse_files(&read_fixture("sde-legacy")).unwrap().0;
+
+        assert_eq!(parse_files(&read_fixture("sde-jsonl")).unwrap().0, legacy);
+        assert_eq!(parse_files(&read_fixture("sde-yaml")).unwrap().0, legacy);
+    }
+
+    /// The JSONL fixture, with `edit` applied to one table.
+    fn edit_jsonl(table: &str, edit: impl Fn(&str) -> String) -> Vec<(PathBuf, String)> {
+        let mut files = read_fixture("sde-jsonl");
+
+        for (path, contents) in &mut files {
+            if path == Path::new(table) {
+                *contents = edit(contents);
+            }
+        }
+
+        files
+    }
+
+    #[test]
+    fn warns_about_fixable_problems() {
+        let files = edit_jsonl("mapStargates.jsonl", |c| c.lines().filter(|l| !l.contains("\"_key\": 50000002")).join("\n"));
+
+        let (records, warnings) = parse_files(&files).unwrap();
+
+        assert_eq!(warnings, vec![
+            "stargate 50000001 in system 30000001 leads to stargate 50000002, which doesn't exist; skipping it".to_owned(),
+        ]);
+        assert_eq!(jumps(&records)[0], ("Tanoo", vec![]));
+
+        // Tanoo's gate leads to Jita, but Jita's only gate leads to Lashesih
+        let files = edit_jsonl("mapStargates.jsonl", |c| c.replacen("\"stargateID\": 50000002", "\"stargateID\": 50000004", 1));
+
+        let (_, warnings) = parse_files(&files).unwrap();
+
+        assert_eq!(warnings, vec![
+            "one-way gate from Lashesih (30000002) to Tanoo (30000001)".to_owned(),
+            "one-way gate from Tanoo (30000001) to Jita (30000142)".to_owned(),
+        ]);
+
+        let files = edit_jsonl("mapRegions.jsonl", |c| c.lines().filter(|l| !l.contains("Derelik")).join("\n"));
+
+        let (records, warnings) = parse_files(&files).unwrap();
+
+        assert_eq!(warnings, vec!["region 10000001 has no name, calling it 'region 10000001'".to_owned()]);
+        assert_eq!(records.systems[0].region, "region 10000001");
+        assert_eq!(records.regions[0], ("The Forge".to_owned(), 10000002));
+        assert_eq!(records.regions[1], ("region 10000001".to_owned(), 10000001));
+    }
+
+    #[test]
+    fn fails_on_duplicate_names() {
+        let files = edit_jsonl("mapSolarSystems.jsonl", |c| c.replace("\"en\": \"Jita\"", "\"en\": \"Tanoo\""));
+
+        let err = parse_files(&files).unwrap_err();
+
+        assert_eq!(err.to_string(), "the SDE failed validation with 1 error:\n  - 2 systems are named 'Tanoo': 30000001, 30000142");
+    }
+}
@@ -0,0 +1,175 @@
+use std::{fs::File, io::{BufReader, BufWriter, Read, Write}, path::{Path, PathBuf}};
+
+use anyhow::{Context, bail};
+
+use crate::{sde::{self, UniverseRecords}, systems};
+
+/// Written at the start of every cache file, and bumped whenever `UniverseRecords` changes shape.
+const CACHE_MAGIC: &[u8; 8] = b"YAERP\0\0\x02";
+
+/// The FNV-1a hash of a file, which is stable across builds and platforms.
+pub fn get_file_hash(path: &Path) -> anyhow::Result<u64> {
+    let mut reader = BufReader::new(File::open(path).with_context(|| format!("could not open {}", path.display()))?);
+    let mut buf = vec![0u8; 1 << 16];
+
+    let mut hash = 0xcbf29ce484222325u64;
+
+    loop {
+        let n = reader.read(&mut buf)?;
+
+        if n == 0 {
+            return Ok(hash);
+        }
+
+        for byte in &buf[..n] {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Where parsed SDEs are cached: `YAERP_CACHE_DIR` if it's set, otherwise the platform's cache directory.
+pub fn get_cache_dir() -> PathBuf {
+    let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+
+    if let Some(dir) = var("YAERP_CACHE_DIR") {
+        return dir;
+    }
+
+    var("XDG_CACHE_HOME")
+        .or_else(|| var("HOME").map(|home| home.join(".cache")))
+        .or_else(|| var("LOCALAPPDATA"))
+        .unwrap_or_else(std::env::temp_dir)
+        .join("yaerp")
+}
+
+pub fn get_cache_path(cache_dir: &Path, hash: u64) -> PathBuf {
+    cache_dir.join(format!("universe-{hash:016x}.bin"))
+}
+
+pub fn read_cache(path: &Path) -> anyhow::Result<UniverseRecords> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+
+    if &magic != CACHE_MAGIC {
+        bail!("{} was written by a different version of yaerp", path.display());
+    }
+
+    Ok(bincode::deserialize_from(reader)?)
+}
+
+pub fn write_cache(path: &Path, records: &UniverseRecords) -> anyhow::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    // write to a temporary file first, so that a half-written cache is never read
+    let tmp = path.with_extension("tmp");
+
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+
+    writer.write_all(CACHE_MAGIC)?;
+    bincode::serialize_into(&mut writer, records)?;
+    writer.flush()?;
+
+    drop(writer);
+
+    std::fs::rename(&tmp, path)?;
+
+    Ok(())
+}
+
+/// Loads an SDE zip, from the cache in `cache_dir` if it was parsed before.
+///
+/// The cache is keyed by the zip's hash, so a new SDE is always parsed again. Failing to write the cache isn't an
+/// error, since it only makes the next start slower.
+pub fn load(sde: &Path, cache_dir: &Path) -> anyhow::Result<UniverseRecords> {
+    let cache = get_cache_path(cache_dir, get_file_hash(sde)?);
+
+    if let Ok(records) = read_cache(&cache) {
+        return Ok(records);
+    }
+
+    let (records, warnings) = sde::load_universe(sde).with_context(|| format!("could not load the SDE from {}", sde.display()))?;
+
+    for warning in warnings {
+        eprintln!("warning: {}: {warning}", sde.display());
+    }
+
+    if let Err(e) = write_cache(&cache, &records) {
+        eprintln!("warning: could not cache the SDE at {}: {e}", cache.display());
+    }
+
+    Ok(records)
+}
+
+/// Loads an SDE zip and uses it instead of the universe embedded at build time.
+pub fn load_and_install(sde: &Path) -> anyhow::Result<()> {
+    systems::install(load(sde, &get_cache_dir())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::sde::SystemRecord;
+
+    fn records() -> UniverseRecords {
+        UniverseRecords {
+            systems: vec![SystemRecord {
+                name: "Jita".to_owned(),
+                security: 0.9459,
+                id: 30000142,
+                const_id: 20000020,
+                constellation: "Kimotoro".to_owned(),
+                region_id: 10000002,
+                region: "The Forge".to_owned(),
+                position: [1.0, 2.0, 3.0],
+                jumps: vec![30000144],
+                gates: vec![(30000144, [4.0, 5.0, 6.0])],
+            }],
+            regions: vec![("The Forge".to_owned(), 10000002)],
+            gate_offsets: vec![0, 0],
+            gate_targets: vec![],
+        }
+    }
+
+    #[test]
+    fn cache_round_trips() {
+        let dir = std::env::temp_dir().join(format!("yaerp-cache-test-{}", std::process::id()));
+        let path = get_cache_path(&dir, 0x1234);
+
+        write_cache(&path, &records()).unwrap();
+
+        let read = read_cache(&path).unwrap();
+
+        assert_eq!(read.systems[0].name, "Jita");
+        assert_eq!(read.systems[0].gates, vec![(30000144, [4.0, 5.0, 6.0])]);
+        assert_eq!(read.regions, vec![("The Forge".to_owned(), 10000002)]);
+
+        std::fs::write(&path, b"not a cache").unwrap();
+
+        assert!(read_cache(&path).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hashes_file_contents() {
+        let dir = std::env::temp_dir().join(format!("yaerp-hash-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("file");
+
+        // FNV-1a of "a"
+        std::fs::write(&path, b"a").unwrap();
+        assert_eq!(get_file_hash(&path).unwrap(), 0xaf63dc4c8601ec8c);
+
+        std::fs::write(&path, b"b").unwrap();
+        assert_ne!(get_file_hash(&path).unwrap(), 0xaf63dc4c8601ec8c);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,342 @@
+use std::{collections::{BinaryHeap, HashMap}, cmp::Ordering, hash::Hash, marker::PhantomData};
+
+pub type Cost = f64;
+
+/// A single hop in a path, stored as `(to, from, via)`.
+pub type Hop<N, E> = (N, N, E);
+
+#[derive(Debug, Clone, Copy)]
+struct State<N> {
+    cost: Cost,
+    node: N,
+}
+
+impl<N> PartialEq for State<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost.total_cmp(&other.cost) == Ordering::Equal
+    }
+}
+
+impl<N> Eq for State<N> {}
+
+impl<N> PartialOrd for State<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N> Ord for State<N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap, so the ordering is reversed to pop the cheapest node first
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Where a search keeps the cheapest known way to reach each node, as `(parent, via, total cost)`.
+pub trait ParentMap<N, E>: Default {
+    fn get(&self, node: N) -> Option<&(N, E, Cost)>;
+
+    fn insert(&mut self, node: N, parent: (N, E, Cost));
+}
+
+impl<N: Copy + Eq + Hash, E> ParentMap<N, E> for HashMap<N, (N, E, Cost)> {
+    fn get(&self, node: N) -> Option<&(N, E, Cost)> {
+        HashMap::get(self, &node)
+    }
+
+    fn insert(&mut self, node: N, parent: (N, E, Cost)) {
+        HashMap::insert(self, node, parent);
+    }
+}
+
+/// Parents for nodes that are small integers, like a `SystemIndex`, kept in a flat array instead of a hash map.
+#[derive(Debug, Clone)]
+pub struct DenseParents<E>(Vec<Option<(u32, E, Cost)>>);
+
+impl<E> Default for DenseParents<E> {
+    fn default() -> Self {
+        Self(Vec::new())
+    }
+}
+
+impl<E> ParentMap<u32, E> for DenseParents<E> {
+    fn get(&self, node: u32) -> Option<&(u32, E, Cost)> {
+        self.0.get(node as usize)?.as_ref()
+    }
+
+    fn insert(&mut self, node: u32, parent: (u32, E, Cost)) {
+        let idx = node as usize;
+
+        if self.0.len() <= idx {
+            self.0.resize_with(idx + 1, || None);
+        }
+
+        self.0[idx] = Some(parent);
+    }
+}
+
+/// The result of a search: the cheapest known way to reach every settled node from `from`.
+#[derive(Debug, Clone)]
+pub struct ShortestPaths<N, E, P = HashMap<N, (N, E, Cost)>> {
+    pub from: N,
+    parents: P,
+    _edge: PhantomData<E>,
+}
+
+impl<N: Copy + Eq, E: Copy, P: ParentMap<N, E>> ShortestPaths<N, E, P> {
+    /// The total cost to reach `to`, or None if it was never reached.
+    pub fn cost(&self, to: N) -> Option<Cost> {
+        if to == self.from {
+            return Some(0.0);
+        }
+
+        self.parents.get(to).map(|(_, _, cost)| *cost)
+    }
+
+    /// The hops from `from` to `to`, in travel order.
+    pub fn path(&self, to: N) -> Option<Vec<Hop<N, E>>> {
+        let mut path = Vec::new();
+
+        let mut curr = to;
+
+        while curr != self.from {
+            let (parent, via, _) = self.parents.get(curr)?;
+
+            path.push((curr, *parent, *via));
+            curr = *parent;
+        }
+
+        path.reverse();
+
+        Some(path)
+    }
+}
+
+/// Runs Dijkstra's algorithm from `from`.
+///
+/// `edges` returns the outgoing edges of a node as `(neighbour, via, cost)`. Costs must not be negative.
+/// The search stops as soon as a node for which `done` returns true is settled, since its cost can no longer change.
+pub fn dijkstra<N, E, I>(
+    from: N,
+    edges: impl FnMut(N) -> I,
+    done: impl FnMut(N) -> bool,
+) -> ShortestPaths<N, E>
+where
+    N: Copy + Eq + Hash,
+    E: Copy,
+    I: IntoIterator<Item = (N, E, Cost)>,
+{
+    dijkstra_with(from, edges, done)
+}
+
+/// Like `dijkstra`, but keeps the parents in `P`, so that dense nodes can skip hashing.
+pub fn dijkstra_with<N, E, I, P>(
+    from: N,
+    mut edges: impl FnMut(N) -> I,
+    mut done: impl FnMut(N) -> bool,
+) -> ShortestPaths<N, E, P>
+where
+    N: Copy + Eq,
+    E: Copy,
+    I: IntoIterator<Item = (N, E, Cost)>,
+    P: ParentMap<N, E>,
+{
+    let mut parents = P::default();
+
+    let mut heap = BinaryHeap::new();
+
+    heap.push(State { cost: 0.0, node: from });
+
+    while let Some(State { cost, node }) = heap.pop() {
+        let best = if node == from { 0.0 } else { parents.get(node).map_or(0.0, |(_, _, cost)| *cost) };
+
+        // stale entry, a cheaper way to this node was already settled
+        if cost > best {
+            continue;
+        }
+
+        if done(node) {
+            break;
+        }
+
+        for (neighbour, via, edge_cost) in edges(node) {
+            if neighbour == from {
+                continue;
+            }
+
+            let n_cost = cost + edge_cost;
+
+            let improved = match parents.get(neighbour) {
+                Some((_, _, existing)) => n_cost < *existing,
+                None => true,
+            };
+
+            if improved {
+                parents.insert(neighbour, (node, via, n_cost));
+                heap.push(State { cost: n_cost, node: neighbour });
+            }
+        }
+    }
+
+    ShortestPaths { from, parents, _edge: PhantomData }
+}
+
+/// Finds the cheapest path from `from` to `to`, returning its cost and hops.
+pub fn shortest_path<N, E, I>(
+    from: N,
+    to: N,
+    edges: impl FnMut(N) -> I,
+) -> Option<(Cost, Vec<Hop<N, E>>)>
+where
+    N: Copy + Eq + Hash,
+    E: Copy,
+    I: IntoIterator<Item = (N, E, Cost)>,
+{
+    let paths = dijkstra(from, edges, |n| n == to);
+
+    Some((paths.cost(to)?, paths.path(to)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Jump;
+
+    type Graph = HashMap<u32, Vec<(u32, Jump, Cost)>>;
+
+    fn graph(edges: &[(u32, u32, Jump, Cost)]) -> Graph {
+        let mut g = Graph::new();
+
+        for (from, to, via, cost) in edges {
+            g.entry(*from).or_default().push((*to, *via, *cost));
+        }
+
+        g
+    }
+
+    fn route(g: &Graph, from: u32, to: u32) -> Option<(Cost, Vec<u32>)> {
+        let (cost, path) = shortest_path(from, to, |n| g.get(&n).cloned().unwrap_or_default())?;
+
+        Some((cost, path.into_iter().map(|(to, _, _)| to).collect()))
+    }
+
+    /// Exhaustively enumerates every simple path to find the true minimum cost.
+    fn brute_force(g: &Graph, curr: u32, to: u32, visited: &mut Vec<u32>) -> Option<Cost> {
+        if curr == to {
+            return Some(0.0);
+        }
+
+        visited.push(curr);
+
+        let mut best: Option<Cost> = None;
+
+        for (n, _, c) in g.get(&curr).into_iter().flatten() {
+            if visited.contains(n) {
+                continue;
+            }
+
+            if let Some(rest) = brute_force(g, *n, to, visited) {
+                best = Some(best.map_or(c + rest, |b| b.min(c + rest)));
+            }
+        }
+
+        visited.pop();
+
+        best
+    }
+
+    #[test]
+    fn prefers_cheap_detour_over_penalized_jump() {
+        // 1 -> 2 -> 3 enters a filtered system, 1 -> 4 -> 5 -> 6 -> 3 does not
+        let g = graph(&[
+            (1, 2, Jump::Gate, 1.0),
+            (2, 3, Jump::Gate, 1000.0),
+            (1, 4, Jump::Gate, 1.0),
+            (4, 5, Jump::Gate, 1.0),
+            (5, 6, Jump::Gate, 1.0),
+            (6, 3, Jump::Gate, 1.0),
+        ]);
+
+        assert_eq!(route(&g, 1, 3), Some((4.0, vec![4, 5, 6, 3])));
+    }
+
+    #[test]
+    fn relaxes_nodes_reached_late_with_a_lower_cost() {
+        // a FIFO search settles 3 via the expensive direct edge first
+        let g = graph(&[
+            (1, 3, Jump::Wormhole, 1000.0),
+            (1, 2, Jump::Gate, 1.0),
+            (2, 3, Jump::Ansiblex, 1.0),
+            (3, 4, Jump::Gate, 1.0),
+        ]);
+
+        assert_eq!(route(&g, 1, 4), Some((3.0, vec![2, 3, 4])));
+    }
+
+    #[test]
+    fn keeps_jump_kind_of_each_hop() {
+        let g = graph(&[
+            (1, 2, Jump::Gate, 1.0),
+            (2, 3, Jump::Wormhole, 1.0),
+            (3, 4, Jump::Ansiblex, 1.0),
+            (1, 4, Jump::Gate, 5.0),
+        ]);
+
+        let (_, path) = shortest_path(1, 4, |n| g.get(&n).cloned().unwrap_or_default()).unwrap();
+
+        assert_eq!(path, vec![
+            (2, 1, Jump::Gate),
+            (3, 2, Jump::Wormhole),
+            (4, 3, Jump::Ansiblex),
+        ]);
+    }
+
+    #[test]
+    fn handles_unreachable_and_trivial_routes() {
+        let g = graph(&[
+            (1, 2, Jump::Gate, 1.0),
+            (3, 1, Jump::Gate, 1.0),
+        ]);
+
+        assert_eq!(route(&g, 1, 3), None);
+        assert_eq!(route(&g, 1, 1), Some((0.0, vec![])));
+    }
+
+    #[test]
+    fn matches_brute_force_on_small_graphs() {
+        let mut seed = 0x2545f491u64;
+
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        for _ in 0..50 {
+            let mut edges = Vec::new();
+
+            for from in 0..7 {
+                for to in 0..7 {
+                    if from != to && next() % 3 == 0 {
+                        let via = [Jump::Gate, Jump::Wormhole, Jump::Ansiblex][(next() % 3) as usize];
+                        let cost = if next() % 5 == 0 { 1000.0 } else { (next() % 4 + 1) as Cost };
+
+                        edges.push((from, to, via, cost));
+                    }
+                }
+            }
+
+            let g = graph(&edges);
+
+            let dense: ShortestPaths<_, _, DenseParents<_>> = dijkstra_with(0, |n| g.get(&n).cloned().unwrap_or_default(), |_| false);
+
+            for to in 1..7 {
+                let expected = brute_force(&g, 0, to, &mut Vec::new());
+
+                assert_eq!(route(&g, 0, to).map(|(cost, _)| cost), expected);
+                assert_eq!(dense.cost(to), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! A small HTTP server that answers route, distance, and system requests as JSON, so other tools don't have to start
+//! yaerp and load the universe for every route.
+//!
+//! - `GET /health`
+//! - `GET /systems/<name>`: a system and the jumps out of it
+//! - `GET /route?waypoints=Jita,Dodixie`: a route, in the same format as `--format json`
+//! - `GET /distance?from=Jita&to=Dodixie`: the jumps and cost of the best route between two systems
+//! - `POST /reload`: loads the overlay files again, which also happens whenever one of them changes
+//!
+//! Routes and distances take the same options as the command line, as query parameters: `prefer`, `avoid` (systems),
+//! `avoid_region`, `ns_region`, `no` (`highsec`, `lowsec`, `nullsec`, `jspace`, or `special`), `no_filter`, `cost`
+//! (like `wormhole=3`), `ship_size`, `min_wormhole_life`, and `exact`. Lists can be repeated or comma separated.
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, bail, Context};
+use clap::ValueEnum;
+use itertools::Itertools;
+use serde_json::{json, Value};
+
+use crate::{
+    diagnostics::ParseMode,
+    find_region_by_name, find_system_by_id, find_system_by_name,
+    output::{RouteReport, SystemInfo, JSON_FORMAT_VERSION},
+    overlays::{OverlayFiles, OverlayUniverse},
+    route::{self, CostModel, RoutePreference, RouteRequest},
+    universe::Universe,
+    wormhole::{ShipSize, WormholeFilter},
+    SystemSecurity, SystemValidityChecker,
+};
+
+/// A request that couldn't be answered, with its HTTP status.
+#[derive(Debug)]
+struct HttpError(u16, String);
+
+impl From<anyhow::Error> for HttpError {
+    fn from(err: anyhow::Error) -> Self {
+        HttpError(400, format!("{err:#}"))
+    }
+}
+
+/// Decodes a `%XX` and `+` escaped URL component.
+fn decode(s: &str) -> String {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' if rest.len() >= 2 => match u8::from_str_radix(std::str::from_utf8(&rest[..2]).unwrap_or("xx"), 16) {
+                Ok(decoded) => {
+                    bytes.push(decoded);
+                    rest = &rest[2..];
+                },
+                Err(_) => bytes.push(b'%'),
+            },
+            _ => bytes.push(b),
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// The decoded path and query parameters of a request URL.
+fn split_url(url: &str) -> (String, Vec<(String, String)>) {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+
+    let params = query.split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (key, value) = p.split_once('=').unwrap_or((p, ""));
+
+            (decode(key), decode(value))
+        })
+        .collect();
+
+    (decode(path), params)
+}
+
+/// Every value of a parameter, with comma separated lists split up.
+fn get_all<'a>(params: &'a [(String, String)], key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    params.iter()
+        .filter(move |(k, _)| k == key)
+        .flat_map(|(_, v)| v.split(','))
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+}
+
+fn get_one<'a>(params: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    params.iter().rev().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+fn get_flag(params: &[(String, String)], key: &str) -> anyhow::Result<bool> {
+    match get_one(params, key) {
+        None | Some("false" | "0" | "no") => Ok(false),
+        Some("" | "true" | "1" | "yes") => Ok(true),
+        Some(other) => bail!("expected true or false for '{key}', got '{other}'"),
+    }
+}
+
+/// The filter and costs a route or distance request asked for.
+fn get_route_options(params: &[(String, String)]) -> anyhow::Result<(SystemValidityChecker, CostModel)> {
+    let preference = get_one(params, "prefer")
+        .map(|p| RoutePreference::from_str(p, true).map_err(|_| anyhow!("unknown preference '{p}': expected shorter, safer, or less-secure")))
+        .transpose()?
+        .unwrap_or(RoutePreference::Shorter);
+
+    let jump_costs = get_all(params, "cost").map(route::parse_jump_cost).collect::<anyhow::Result<Vec<_>>>()?;
+
+    let ship_size = get_one(params, "ship_size")
+        .map(|s| ShipSize::from_str(s, true).map_err(|_| anyhow!("unknown ship size '{s}': expected small, medium, large, or xl")))
+        .transpose()?;
+
+    let min_life = get_one(params, "min_wormhole_life")
+        .map(|d| humantime::parse_duration(d).with_context(|| format!("invalid duration '{d}'")))
+        .transpose()?
+        .unwrap_or(Duration::ZERO);
+
+    let checker = SystemValidityChecker {
+        invalid_securities: get_all(params, "no").map(SystemSecurity::from_name).collect::<anyhow::Result<_>>()?,
+        invalid_regions: get_all(params, "avoid_region").map(find_region_by_name).collect::<anyhow::Result<_>>()?,
+        valid_ns_regions: get_all(params, "ns_region").map(find_region_by_name).collect::<anyhow::Result<_>>()?,
+        invalid_systems: get_all(params, "avoid").map(find_system_by_name).collect::<anyhow::Result<_>>()?,
+        strict: !get_flag(params, "no_filter")?,
+        wormholes: WormholeFilter {
+            ship_size,
+            min_life,
+            now: SystemTime::now(),
+        },
+    };
+
+    Ok((checker, CostModel::new(&jump_costs, preference)))
+}
+
+/// Answers requests against a universe that is reloaded whenever its overlay files change.
+pub struct RouteServer {
+    overlays: OverlayUniverse,
+}
+
+impl RouteServer {
+    /// Loads `files` on top of `base`.
+    pub fn new(base: Universe, files: OverlayFiles, mode: ParseMode) -> anyhow::Result<Self> {
+        Ok(Self { overlays: OverlayUniverse::new(base, files, mode)? })
+    }
+
+    /// Loads the overlay files again. The old universe is kept if they can't be loaded.
+    pub fn reload(&mut self) -> anyhow::Result<()> {
+        self.overlays.reload()
+    }
+
+    /// Reloads the overlay files if any of them changed since they were last loaded.
+    pub fn reload_if_changed(&mut self) {
+        self.overlays.reload_if_changed();
+    }
+
+    pub fn get_universe(&self) -> &Universe {
+        self.overlays.get_universe()
+    }
+
+    /// Answers one request, as an HTTP status and a JSON body.
+    pub fn handle(&mut self, method: &str, url: &str) -> (u16, Value) {
+        match self.try_handle(method, url) {
+            Ok(body) => (200, body),
+            Err(HttpError(status, message)) => (status, json!({ "error": message })),
+        }
+    }
+
+    fn try_handle(&mut self, method: &str, url: &str) -> Result<Value, HttpError> {
+        let (path, params) = split_url(url);
+
+        let segments = path.trim_matches('/').split('/').collect_vec();
+
+        match (method, segments.as_slice()) {
+            ("GET", ["health"]) => Ok(json!({ "status": "ok", "version": JSON_FORMAT_VERSION })),
+            ("GET", ["systems", name]) => self.get_system(name),
+            ("GET", ["route"]) => self.get_route(&params),
+            ("GET", ["distance"]) => self.get_distance(&params),
+            ("POST", ["reload"]) => {
+                self.reload().map_err(|e| HttpError(500, format!("{e:#}")))?;
+
+                Ok(json!({ "status": "reloaded" }))
+            },
+            (_, ["health" | "route" | "distance" | "reload"] | ["systems", _]) => Err(HttpError(405, format!("{method} is not allowed on {path}"))),
+            _ => Err(HttpError(404, format!("nothing at {path}"))),
+        }
+    }
+
+    fn get_system(&self, name: &str) -> Result<Value, HttpError> {
+        let id = find_system_by_name(name).map_err(|e| HttpError(404, e.to_string()))?;
+
+        let jumps = self.get_universe().get_jumps(id)
+            .map(|(to, via)| json!({ "to": SystemInfo::from_id(to), "jump": via }))
+            .collect_vec();
+
+        Ok(json!({ "system": SystemInfo::new(find_system_by_id(id)), "jumps": jumps }))
+    }
+
+    fn get_route(&self, params: &[(String, String)]) -> Result<Value, HttpError> {
+        let waypoints = get_all(params, "waypoints").map(find_system_by_name).collect::<anyhow::Result<Vec<_>>>()?;
+
+        let (checker, costs) = get_route_options(params)?;
+
+        let planned = RouteRequest::new(waypoints)
+            .filter(checker.clone())
+            .costs(costs)
+            .exact(get_flag(params, "exact")?)
+            .plan(self.get_universe())?;
+
+        Ok(serde_json::to_value(RouteReport::new(&planned, self.get_universe(), &checker)).map_err(anyhow::Error::from)?)
+    }
+
+    fn get_distance(&self, params: &[(String, String)]) -> Result<Value, HttpError> {
+        let system = |key: &str| -> Result<_, HttpError> {
+            let name = get_one(params, key).ok_or_else(|| HttpError(400, format!("expected a system in '{key}'")))?;
+
+            Ok(find_system_by_name(name)?)
+        };
+
+        let (from, to) = (system("from")?, system("to")?);
+
+        let (checker, costs) = get_route_options(params)?;
+
+        let planned = RouteRequest::new(vec![from, to])
+            .filter(checker)
+            .costs(costs)
+            .plan(self.get_universe())?;
+
+        let reachable = planned.is_valid();
+
+        Ok(json!({
+            "from": SystemInfo::from_id(from),
+            "to": SystemInfo::from_id(to),
+            "jumps": reachable.then(|| planned.get_jump_count()),
+            "cost": reachable.then(|| planned.get_cost()),
+        }))
+    }
+
+    /// Answers requests until the server is shut down.
+    pub fn run(&mut self, server: &tiny_http::Server) {
+        for request in server.incoming_requests() {
+            self.reload_if_changed();
+
+            let (status, body) = self.handle(request.method().as_str(), request.url());
+
+            let response = tiny_http::Response::from_string(body.to_string())
+                .with_status_code(status)
+                .with_header(tiny_http::Header::from_bytes("Content-Type", "application/json").expect("a valid header"));
+
+            if let Err(e) = request.respond(response) {
+                eprintln!("warning: could not respond to a request: {e}");
+            }
+        }
+    }
+}
+
+/// Listens on `addr`, like `127.0.0.1:8080`, and answers requests until the process is stopped.
+pub fn serve(addr: &str, server: &mut RouteServer) -> anyhow::Result<()> {
+    let listener = tiny_http::Server::http(addr).map_err(|e| anyhow!("could not listen on {addr}: {e}"))?;
+
+    eprintln!("listening on http://{}", listener.server_addr());
+
+    server.run(&listener);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::*;
+    use crate::wormhole::WormholeInfo;
+
+    #[test]
+    fn decodes_urls() {
+        let (path, params) = split_url("/systems/New%20Caldari?waypoints=Jita,Perimeter&avoid=a+b&flag");
+
+        assert_eq!(path, "/systems/New Caldari");
+        assert_eq!(params, vec![
+            ("waypoints".to_owned(), "Jita,Perimeter".to_owned()),
+            ("avoid".to_owned(), "a b".to_owned()),
+            ("flag".to_owned(), String::new()),
+        ]);
+        assert_eq!(decode("100%"), "100%");
+    }
+
+    #[test]
+    fn answers_requests() {
+        let mut server = RouteServer::new(Universe::stargates(), OverlayFiles::default(), ParseMode::Strict).unwrap();
+
+        let (status, body) = server.handle("GET", "/route?waypoints=Jita,Perimeter");
+
+        assert_eq!(status, 200);
+        assert_eq!(body["total_jumps"], 1);
+        assert_eq!(body["legs"][0]["jumps"][0]["jump"], "gate");
+
+        let (status, body) = server.handle("GET", "/distance?from=Jita&to=Perimeter&avoid=Perimeter");
+
+        assert_eq!((status, body["jumps"].clone()), (200, Value::Null));
+
+        assert_eq!(server.handle("GET", "/systems/Jita").1["system"]["name"], "Jita");
+        assert_eq!(server.handle("GET", "/systems/Nowhere123").0, 404);
+        assert_eq!(server.handle("GET", "/route?waypoints=Jita,Perimeter&prefer=sideways").0, 400);
+        assert_eq!(server.handle("DELETE", "/route").0, 405);
+        assert_eq!(server.handle("GET", "/").0, 404);
+    }
+
+    #[test]
+    fn expired_holes_are_unreachable() {
+        let jita = find_system_by_name("Jita").unwrap();
+        let thera = find_system_by_name("Thera").unwrap();
+
+        let mut universe = Universe::stargates();
+
+        universe.add_wormhole(jita, thera, WormholeInfo { expires: Some(SystemTime::now() - Duration::from_secs(60)), ..Default::default() });
+
+        let mut server = RouteServer::new(universe, OverlayFiles::default(), ParseMode::Strict).unwrap();
+
+        assert_eq!(server.handle("GET", "/distance?from=Jita&to=Thera").1["jumps"], Value::Null);
+    }
+
+    #[test]
+    fn reloads_changed_overlays() {
+        let dir = std::env::temp_dir().join(format!("yaerp-server-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let file = dir.join("connections.toml");
+        std::fs::write(&file, "").unwrap();
+
+        let files = OverlayFiles { connections: vec![file.clone()], ..Default::default() };
+        let mut server = RouteServer::new(Universe::stargates(), files, ParseMode::Strict).unwrap();
+
+        assert_eq!(server.handle("GET", "/distance?from=Jita&to=Thera").1["jumps"], Value::Null);
+
+        std::fs::write(&file, "[[connection]]\nfrom = \"Jita\"\nto = \"Thera\"\n").unwrap();
+
+        // the modification time might not have changed, so force it
+        server.reload().unwrap();
+
+        assert_eq!(server.handle("GET", "/distance?from=Jita&to=Thera").1["jumps"], 1);
+
+        std::fs::write(&file, "[[connection]]\nfrom = \"Nowhere123\"\n").unwrap();
+
+        assert!(server.reload().is_err());
+        assert_eq!(server.handle("GET", "/distance?from=Jita&to=Thera").1["jumps"], 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn serves_over_localhost() {
+        let listener = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let addr = listener.server_addr().to_ip().unwrap();
+
+        std::thread::spawn(move || {
+            let mut server = RouteServer::new(Universe::stargates(), OverlayFiles::default(), ParseMode::Strict).unwrap();
+
+            server.run(&listener);
+        });
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+
+        stream.write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.contains("application/json"));
+        assert!(response.ends_with(&json!({ "status": "ok", "version": JSON_FORMAT_VERSION }).to_string()));
+    }
+}
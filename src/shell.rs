@@ -0,0 +1,466 @@
+//! An interactive prompt that keeps the universe and route options loaded between queries, for planning many routes
+//! in a row:
+//!
+//! ```text
+//! yaerp> avoid Uedama
+//! yaerp> route Jita Dodixie
+//! yaerp> near Jita 3
+//! ```
+//!
+//! Names with spaces can be quoted, like `route Jita "New Caldari"`, except after `avoid`, `unavoid`, and `load-wh`,
+//! which take the rest of the line.
+
+use std::{path::PathBuf, time::SystemTime};
+
+use anyhow::{bail, Context};
+use clap::ValueEnum;
+use itertools::Itertools;
+use rustyline::{
+    completion::{Completer, FilenameCompleter, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+    Editor, Helper,
+};
+
+use crate::{
+    find_region_by_name, find_system_by_name, get_system_name,
+    output,
+    overlays::OverlayUniverse,
+    route::{self, RoutePreference, RouteRequest},
+    sde_cache, systems, SystemId, SystemSecurity,
+};
+
+/// How far `near` looks when it isn't given a number of jumps.
+pub const DEFAULT_NEAR_JUMPS: usize = 5;
+
+/// Every command, with its arguments and what it does.
+const COMMANDS: &[(&str, &str, &str)] = &[
+    ("route", "<system> <system>...", "plan a route through the systems, like on the command line"),
+    ("near", "<system> [jumps]", "list the systems within some jumps (5 by default)"),
+    ("avoid", "<system, region, or security>", "keep routes out of a system, region, or security (like lowsec)"),
+    ("unavoid", "<system, region, or security>", "stop avoiding it"),
+    ("prefer", "<shorter, safer, or less-secure>", "change what routes prefer"),
+    ("load-wh", "<file>", "load a file of copy+pasted wormhole bookmarks"),
+    ("reload", "", "load every wormhole, ansiblex, and other overlay file again"),
+    ("show", "filters", "list what routes avoid and prefer"),
+    ("help", "", "list the commands"),
+    ("exit", "", "leave the shell (or press Ctrl-D)"),
+];
+
+/// Something `avoid` can keep routes out of.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Avoidable {
+    Security(SystemSecurity),
+    System(SystemId),
+    Region(i64),
+}
+
+impl Avoidable {
+    /// A security class, a system, or a region, checked in that order.
+    fn find(name: &str) -> anyhow::Result<Self> {
+        if let Ok(security) = SystemSecurity::from_name(name) {
+            return Ok(Avoidable::Security(security));
+        }
+
+        let system_error = match find_system_by_name(name) {
+            Ok(id) => return Ok(Avoidable::System(id)),
+            Err(e) => e,
+        };
+
+        find_region_by_name(name)
+            .map(Avoidable::Region)
+            .map_err(|region_error| anyhow::anyhow!("{system_error}, and {region_error}"))
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Avoidable::Security(security) => security.name().to_owned(),
+            Avoidable::System(id) => get_system_name(*id).to_owned(),
+            Avoidable::Region(id) => get_region_name(*id).to_owned(),
+        }
+    }
+}
+
+fn get_region_name(id: i64) -> &'static str {
+    systems::get_regions().iter().find(|(_, r)| *r == id).map_or("unknown region", |(name, _)| name)
+}
+
+/// Splits a line into words. Words can be quoted with `"`, and `\` escapes the next character.
+fn split_words(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = None::<String>;
+    let mut quoted = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.get_or_insert_with(String::new);
+            },
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            c if c.is_whitespace() && !quoted => words.extend(word.take()),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quoted {
+        bail!("unclosed quote");
+    }
+
+    words.extend(word);
+
+    Ok(words)
+}
+
+/// Adds `item` to `list` once, or takes it out.
+fn set_listed<T: PartialEq>(list: &mut Vec<T>, item: T, listed: bool) {
+    list.retain(|i| *i != item);
+
+    if listed {
+        list.push(item);
+    }
+}
+
+/// A universe and route options that persist between commands.
+pub struct Shell {
+    overlays: OverlayUniverse,
+    /// The options every route starts from. Its waypoints aren't used.
+    request: RouteRequest,
+}
+
+impl Shell {
+    pub fn new(overlays: OverlayUniverse, request: RouteRequest) -> Self {
+        Self { overlays, request }
+    }
+
+    pub fn get_request(&self) -> &RouteRequest {
+        &self.request
+    }
+
+    /// Runs one line, and returns what to print.
+    pub fn execute(&mut self, line: &str) -> anyhow::Result<String> {
+        let words = split_words(line)?;
+
+        let Some((command, args)) = words.split_first() else {
+            return Ok(String::new());
+        };
+
+        // `avoid The Forge` shouldn't need quotes
+        let rest = args.join(" ");
+
+        match command.to_lowercase().as_str() {
+            "route" => self.route(args),
+            "near" => self.near(args),
+            "avoid" => self.avoid(&rest, true),
+            "unavoid" => self.avoid(&rest, false),
+            "prefer" => {
+                self.request.costs.preference = RoutePreference::from_str(&rest, true)
+                    .map_err(|_| anyhow::anyhow!("unknown preference '{rest}': expected shorter, safer, or less-secure"))?;
+
+                Ok(format!("Routes now prefer {rest}\n"))
+            },
+            "load-wh" => {
+                if rest.is_empty() {
+                    bail!("expected a file of wormhole bookmarks");
+                }
+
+                self.overlays.add_wormholes(PathBuf::from(&rest))?;
+
+                Ok(format!("Loaded {rest}\n"))
+            },
+            "reload" => {
+                self.overlays.reload()?;
+
+                Ok("Reloaded the overlay files\n".to_owned())
+            },
+            "show" if rest == "filters" => Ok(self.show_filters()),
+            "show" => bail!("expected 'show filters'"),
+            "help" => Ok(get_help()),
+            other => bail!("unknown command '{other}': type 'help' for a list of commands"),
+        }
+    }
+
+    fn route(&self, args: &[String]) -> anyhow::Result<String> {
+        let waypoints = args.iter()
+            .map(|s| find_system_by_name(s))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut request = self.request.clone();
+
+        request.waypoints = waypoints;
+
+        // wormholes keep dying while the shell is open
+        request.filter.wormholes.now = SystemTime::now();
+
+        let planned = request.plan(self.overlays.get_universe())?;
+
+        let mut text = String::new();
+
+        output::write_text(&mut text, &planned, self.overlays.get_universe(), &request.filter, request.mode)?;
+
+        Ok(text)
+    }
+
+    fn near(&self, args: &[String]) -> anyhow::Result<String> {
+        let (from, max_jumps) = match args {
+            [from] => (from, DEFAULT_NEAR_JUMPS),
+            [from, jumps] => (from, jumps.parse().with_context(|| format!("invalid number of jumps '{jumps}'"))?),
+            _ => bail!("expected 'near <system> [jumps]'"),
+        };
+
+        let from = find_system_by_name(from)?;
+
+        let mut filter = self.request.filter.clone();
+
+        filter.wormholes.now = SystemTime::now();
+
+        let nearby = route::find_nearby(self.overlays.get_universe(), from, max_jumps, &filter)?;
+
+        let mut text = String::new();
+
+        output::write_nearby_text(&mut text, from, max_jumps, &nearby)?;
+
+        Ok(text)
+    }
+
+    fn avoid(&mut self, name: &str, avoid: bool) -> anyhow::Result<String> {
+        if name.is_empty() {
+            bail!("expected a system, region, or security");
+        }
+
+        let target = Avoidable::find(name)?;
+        let filter = &mut self.request.filter;
+
+        match target {
+            Avoidable::Security(security) => set_listed(&mut filter.invalid_securities, security, avoid),
+            Avoidable::System(id) => set_listed(&mut filter.invalid_systems, id, avoid),
+            Avoidable::Region(id) => set_listed(&mut filter.invalid_regions, id, avoid),
+        }
+
+        Ok(format!("{} {}\n", if avoid { "Avoiding" } else { "No longer avoiding" }, target.name()))
+    }
+
+    fn show_filters(&self) -> String {
+        let filter = &self.request.filter;
+
+        let list = |names: Vec<String>| if names.is_empty() { "none".to_owned() } else { names.join(", ") };
+
+        let mut lines = vec![
+            format!("Avoided securities: {}", list(filter.invalid_securities.iter().map(|s| s.name().to_owned()).collect())),
+            format!("Avoided regions: {}", list(filter.invalid_regions.iter().map(|r| get_region_name(*r).to_owned()).collect())),
+            format!("Avoided systems: {}", list(filter.invalid_systems.iter().map(|s| get_system_name(*s).to_owned()).collect())),
+        ];
+
+        if !filter.valid_ns_regions.is_empty() {
+            lines.push(format!("Only nullsec in: {}", list(filter.valid_ns_regions.iter().map(|r| get_region_name(*r).to_owned()).collect())));
+        }
+
+        lines.push(format!("Filtered systems are {}", if filter.strict { "never entered" } else { "left as soon as possible" }));
+
+        lines.push(format!("Preference: {}", self.request.costs.preference.to_possible_value().expect("no skipped variants").get_name()));
+
+        if let Some(size) = filter.wormholes.ship_size {
+            lines.push(format!("Ship size: {}", size.name()));
+        }
+
+        if !filter.wormholes.min_life.is_zero() {
+            lines.push(format!("Minimum wormhole life: {}", humantime::format_duration(filter.wormholes.min_life)));
+        }
+
+        let files = self.overlays.get_files();
+
+        if !files.is_empty() {
+            lines.push(format!("Overlay files: {}", files.get_paths().map(|p| p.display()).join(", ")));
+        }
+
+        lines.into_iter().map(|line| line + "\n").collect()
+    }
+
+    /// Reads and runs commands until `exit` or Ctrl-D, with tab completion and history that's kept between sessions.
+    pub fn run(&mut self) -> anyhow::Result<()> {
+        let mut editor = Editor::<ShellHelper, DefaultHistory>::new()?;
+
+        editor.set_helper(Some(ShellHelper { files: FilenameCompleter::new() }));
+
+        let history = sde_cache::get_cache_dir().join("shell-history");
+
+        // there's no history the first time
+        let _ = editor.load_history(&history);
+
+        println!("Type 'help' for a list of commands.");
+
+        loop {
+            let line = match editor.readline("yaerp> ") {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            editor.add_history_entry(line.as_str())?;
+
+            if matches!(line.trim(), "exit" | "quit") {
+                break;
+            }
+
+            match self.execute(&line) {
+                Ok(text) => print!("{text}"),
+                Err(e) => eprintln!("error: {e:#}"),
+            }
+        }
+
+        if let Err(e) = std::fs::create_dir_all(sde_cache::get_cache_dir()).map_err(ReadlineError::from).and_then(|_| editor.save_history(&history)) {
+            eprintln!("warning: could not save the shell history to {}: {e}", history.display());
+        }
+
+        Ok(())
+    }
+}
+
+fn get_help() -> String {
+    let width = COMMANDS.iter().map(|(name, args, _)| name.len() + args.len() + 1).max().unwrap_or(0);
+
+    COMMANDS.iter()
+        .map(|(name, args, about)| format!("  {:width$}  {about}\n", format!("{name} {args}")))
+        .collect()
+}
+
+/// The words that can complete the word that starts at `start` in `line`, and ends at the cursor.
+fn get_completions(line: &str, start: usize) -> Vec<String> {
+    let word = line[start..].trim_start_matches('"').to_lowercase();
+
+    let command = line[..start].split_whitespace().next();
+
+    let Some(command) = command else {
+        return COMMANDS.iter()
+            .map(|(name, _, _)| *name)
+            .filter(|name| name.starts_with(&word))
+            .map(str::to_owned)
+            .collect();
+    };
+
+    let (keywords, systems) = match command {
+        "avoid" | "unavoid" => (SystemSecurity::ALL.iter().map(|s| s.name()).collect_vec(), true),
+        "route" | "near" => (Vec::new(), true),
+        "show" => (vec!["filters"], false),
+        _ => return Vec::new(),
+    };
+
+    let systems = systems::get_systems().iter()
+        .map(|sys| sys.name)
+        .filter(|name| systems && name.to_lowercase().starts_with(&word))
+        .sorted();
+
+    keywords.into_iter()
+        .filter(|name| name.starts_with(&word))
+        .chain(systems)
+        .map(|name| if name.contains(' ') { format!("\"{name}\"") } else { name.to_owned() })
+        .collect()
+}
+
+struct ShellHelper {
+    files: FilenameCompleter,
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, ctx: &rustyline::Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        if line.trim_start().starts_with("load-wh ") {
+            return self.files.complete(line, pos, ctx);
+        }
+
+        let start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| i + 1);
+
+        let pairs = get_completions(&line[..pos], start)
+            .into_iter()
+            .map(|word| Pair { display: word.clone(), replacement: word })
+            .collect();
+
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diagnostics::ParseMode, overlays::OverlayFiles, universe::Universe};
+
+    fn shell() -> Shell {
+        let overlays = OverlayUniverse::new(Universe::stargates(), OverlayFiles::default(), ParseMode::Strict).unwrap();
+
+        Shell::new(overlays, RouteRequest::new(Vec::new()))
+    }
+
+    #[test]
+    fn splits_quoted_words() {
+        assert_eq!(split_words(r#"route Jita "New Caldari" Old\ Man\ Star"#).unwrap(), ["route", "Jita", "New Caldari", "Old Man Star"]);
+        assert_eq!(split_words(r#"  avoid "" "#).unwrap(), ["avoid", ""]);
+        assert!(split_words(r#"route "Jita"#).is_err());
+    }
+
+    #[test]
+    fn filters_persist_between_commands() {
+        let mut shell = shell();
+
+        assert!(shell.execute("route Jita Perimeter").unwrap().contains("(1 jumps, cost 1.00)"));
+
+        assert_eq!(shell.execute("avoid Perimeter").unwrap(), "Avoiding Perimeter\n");
+        assert_eq!(shell.execute("avoid lowsec").unwrap(), "Avoiding lowsec\n");
+
+        assert!(shell.execute("show filters").unwrap().contains("Avoided systems: Perimeter\n"));
+        assert!(!shell.execute("route Jita Perimeter").unwrap().contains("(1 jumps"));
+
+        shell.execute("unavoid Perimeter").unwrap();
+
+        assert_eq!(shell.get_request().filter.invalid_systems, Vec::<SystemId>::new());
+        assert_eq!(shell.get_request().filter.invalid_securities, vec![SystemSecurity::Lowsec]);
+        assert!(shell.execute("route Jita Perimeter").unwrap().contains("(1 jumps"));
+    }
+
+    #[test]
+    fn rejects_bad_commands() {
+        let mut shell = shell();
+
+        assert!(shell.execute("fly Jita").is_err());
+        assert!(shell.execute("near Jita many").is_err());
+        assert!(shell.execute("avoid Nowhere123").is_err());
+        assert!(shell.execute("load-wh /nonexistent/bookmarks.txt").is_err());
+        assert!(shell.get_request().filter.invalid_systems.is_empty());
+        assert_eq!(shell.execute("").unwrap(), "");
+    }
+
+    #[test]
+    fn near_lists_systems_by_jumps() {
+        let text = shell().execute("near Jita 1").unwrap();
+
+        assert!(text.contains("Perimeter  The Forge  gate"));
+        assert!(text.lines().skip(2).all(|line| line.trim_start().starts_with("1  ")));
+    }
+
+    #[test]
+    fn completes_commands_and_systems() {
+        assert_eq!(get_completions("ro", 0), ["route"]);
+        assert!(get_completions("route Ji", 6).contains(&"Jita".to_owned()));
+        assert!(get_completions("avoid low", 6).contains(&"lowsec".to_owned()));
+        assert_eq!(get_completions("show f", 5), ["filters"]);
+    }
+}
@@ -1,6 +1,20 @@
+use std::sync::OnceLock;
+
+use crate::sde::UniverseRecords;
 
 pub type SystemId = i64;
 
+/// A system's position in `get_systems()`, which is how the routing graph refers to it.
+pub type SystemIndex = u32;
+
+#[derive(Debug, Clone)]
+pub struct Stargate {
+    /// The system this gate leads to
+    pub destination: SystemId,
+    /// The gate's position in its system, in meters
+    pub position: [f64; 3],
+}
+
 #[derive(Debug, Clone)]
 pub struct System {
     pub id: SystemId,
@@ -10,7 +24,10 @@ pub struct System {
     pub constellation: &'static str,
     pub region_id: i64,
     pub region: &'static str,
+    /// The system's position in space, in meters
+    pub position: [f64; 3],
     pub jumps: &'static [SystemId],
+    pub gates: &'static [Stargate],
 }
 
 // rust analyzer doesn't like it when the systems are actually loaded
@@ -19,7 +36,97 @@ pub struct System {
 include!(concat!(env!("OUT_DIR"), "/systems.rs"));
 
 #[cfg(empty_systems)]
-pub const SYSTEMS: &'static [System] = &[];
+pub const EMBEDDED_SYSTEMS: &[System] = &[];
+
+#[cfg(empty_systems)]
+pub const EMBEDDED_REGIONS: &[(&str, i64)] = &[];
 
 #[cfg(empty_systems)]
-pub const REGIONS: &'static [(&'static str, i64)] = &[];
+pub const EMBEDDED_GATE_OFFSETS: &[u32] = &[0];
+
+#[cfg(empty_systems)]
+pub const EMBEDDED_GATE_TARGETS: &[SystemIndex] = &[];
+
+struct LoadedUniverse {
+    systems: &'static [System],
+    regions: &'static [(&'static str, i64)],
+    gate_offsets: &'static [u32],
+    gate_targets: &'static [SystemIndex],
+}
+
+static UNIVERSE: OnceLock<LoadedUniverse> = OnceLock::new();
+
+fn get_universe() -> &'static LoadedUniverse {
+    UNIVERSE.get_or_init(|| LoadedUniverse {
+        systems: EMBEDDED_SYSTEMS,
+        regions: EMBEDDED_REGIONS,
+        gate_offsets: EMBEDDED_GATE_OFFSETS,
+        gate_targets: EMBEDDED_GATE_TARGETS,
+    })
+}
+
+/// Every solar system, sorted by ID. This is the data baked in at build time, unless `install` was called first.
+pub fn get_systems() -> &'static [System] {
+    get_universe().systems
+}
+
+/// Every region as `(name, id)`, sorted by name.
+pub fn get_regions() -> &'static [(&'static str, i64)] {
+    get_universe().regions
+}
+
+/// The stargates as a compressed sparse row graph, as `(offsets, targets)`: the gates out of the system at index `i`
+/// lead to `targets[offsets[i]..offsets[i + 1]]`.
+pub fn get_gate_graph() -> (&'static [u32], &'static [SystemIndex]) {
+    let universe = get_universe();
+
+    (universe.gate_offsets, universe.gate_targets)
+}
+
+/// The index of a system in `get_systems()`, or None if it doesn't exist.
+pub fn get_system_index(id: SystemId) -> Option<SystemIndex> {
+    get_systems().binary_search_by_key(&id, |s| s.id).ok().map(|idx| idx as SystemIndex)
+}
+
+/// Replaces the embedded universe with one loaded at runtime.
+///
+/// This has to happen before anything looks up a system, and can only happen once. The data lives for the rest of the
+/// program, like the embedded data does.
+pub fn install(records: UniverseRecords) -> anyhow::Result<()> {
+    fn leak<T>(v: Vec<T>) -> &'static [T] {
+        Box::leak(v.into_boxed_slice())
+    }
+
+    fn leak_str(s: String) -> &'static str {
+        Box::leak(s.into_boxed_str())
+    }
+
+    let systems = records.systems.into_iter()
+        .map(|sys| System {
+            id: sys.id,
+            name: leak_str(sys.name),
+            security: sys.security,
+            constellation_id: sys.const_id,
+            constellation: leak_str(sys.constellation),
+            region_id: sys.region_id,
+            region: leak_str(sys.region),
+            position: sys.position,
+            jumps: leak(sys.jumps),
+            gates: leak(sys.gates.into_iter().map(|(destination, position)| Stargate { destination, position }).collect()),
+        })
+        .collect();
+
+    let regions = records.regions.into_iter()
+        .map(|(name, id)| (leak_str(name), id))
+        .collect();
+
+    let universe = LoadedUniverse {
+        systems: leak(systems),
+        regions: leak(regions),
+        gate_offsets: leak(records.gate_offsets),
+        gate_targets: leak(records.gate_targets),
+    };
+
+    UNIVERSE.set(universe)
+        .map_err(|_| anyhow::anyhow!("the universe was already loaded"))
+}
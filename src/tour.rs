@@ -0,0 +1,273 @@
+use crate::search::Cost;
+
+/// The most intermediate waypoints that will be ordered exactly. Held-Karp needs `2^n * n` states, so anything past
+/// this is ordered heuristically instead.
+pub const EXACT_WAYPOINT_LIMIT: usize = 15;
+
+/// Picks the cheapest order to visit every waypoint.
+///
+/// `costs[a][b]` is the cost of travelling from waypoint `a` to waypoint `b`. The first and last waypoints are the start
+/// and end, and are never moved. Returns the waypoint indices in visiting order.
+pub fn best_order(costs: &[Vec<Cost>]) -> Vec<usize> {
+    let n = costs.len();
+
+    if n <= 3 {
+        return (0..n).collect();
+    }
+
+    if n - 2 <= EXACT_WAYPOINT_LIMIT {
+        held_karp(costs)
+    } else {
+        improve(costs, nearest_neighbour(costs))
+    }
+}
+
+pub fn get_order_cost(costs: &[Vec<Cost>], order: &[usize]) -> Cost {
+    order.windows(2).map(|w| costs[w[0]][w[1]]).sum()
+}
+
+/// Exact dynamic program over subsets of the intermediate waypoints.
+fn held_karp(costs: &[Vec<Cost>]) -> Vec<usize> {
+    let n = costs.len();
+    let end = n - 1;
+    // intermediate waypoint `j` is index `j + 1` in `costs`
+    let m = n - 2;
+    let full = (1usize << m) - 1;
+
+    let mut best = vec![Cost::INFINITY; (full + 1) * m];
+    let mut parent = vec![usize::MAX; (full + 1) * m];
+
+    for j in 0..m {
+        best[(1 << j) * m + j] = costs[0][j + 1];
+    }
+
+    for mask in 1..=full {
+        for last in 0..m {
+            if mask & (1 << last) == 0 {
+                continue;
+            }
+
+            let curr = best[mask * m + last];
+
+            if curr == Cost::INFINITY {
+                continue;
+            }
+
+            for next in 0..m {
+                if mask & (1 << next) != 0 {
+                    continue;
+                }
+
+                let n_mask = mask | (1 << next);
+                let n_cost = curr + costs[last + 1][next + 1];
+
+                if n_cost < best[n_mask * m + next] {
+                    best[n_mask * m + next] = n_cost;
+                    parent[n_mask * m + next] = last;
+                }
+            }
+        }
+    }
+
+    let mut last = (0..m)
+        .min_by(|l, r| {
+            let l = best[full * m + l] + costs[l + 1][end];
+            let r = best[full * m + r] + costs[r + 1][end];
+            l.total_cmp(&r)
+        })
+        .unwrap();
+
+    let mut order = vec![end];
+    let mut mask = full;
+
+    while last != usize::MAX {
+        order.push(last + 1);
+
+        let prev = parent[mask * m + last];
+        mask &= !(1 << last);
+        last = prev;
+    }
+
+    order.push(0);
+    order.reverse();
+
+    order
+}
+
+/// Greedily visits the closest unvisited waypoint next.
+fn nearest_neighbour(costs: &[Vec<Cost>]) -> Vec<usize> {
+    let n = costs.len();
+
+    let mut order = vec![0];
+    let mut remaining = (1..n - 1).collect::<Vec<_>>();
+
+    while !remaining.is_empty() {
+        let curr = *order.last().unwrap();
+
+        let (idx, _) = remaining.iter()
+            .enumerate()
+            .min_by(|(_, l), (_, r)| costs[curr][**l].total_cmp(&costs[curr][**r]))
+            .unwrap();
+
+        order.push(remaining.swap_remove(idx));
+    }
+
+    order.push(n - 1);
+
+    order
+}
+
+/// Applies 2-opt and Or-opt moves until neither can improve the order any further.
+///
+/// Costs may be asymmetric (filtering only applies when entering a system), so every candidate is costed in full.
+fn improve(costs: &[Vec<Cost>], mut order: Vec<usize>) -> Vec<usize> {
+    let n = order.len();
+
+    let mut curr = get_order_cost(costs, &order);
+
+    loop {
+        let mut improved = false;
+
+        // 2-opt: reverse order[i..=j]
+        for i in 1..n - 2 {
+            for j in i + 1..n - 1 {
+                let mut candidate = order.clone();
+                candidate[i..=j].reverse();
+
+                let cost = get_order_cost(costs, &candidate);
+
+                if cost < curr {
+                    order = candidate;
+                    curr = cost;
+                    improved = true;
+                }
+            }
+        }
+
+        // Or-opt: move a run of up to 3 waypoints somewhere else
+        for len in 1..=3 {
+            for i in 1..n - len {
+                for dst in 1..n - len {
+                    if dst == i {
+                        continue;
+                    }
+
+                    let mut candidate = order.clone();
+                    let segment = candidate.drain(i..i + len).collect::<Vec<_>>();
+                    candidate.splice(dst..dst, segment);
+
+                    let cost = get_order_cost(costs, &candidate);
+
+                    if cost < curr {
+                        order = candidate;
+                        curr = cost;
+                        improved = true;
+                    }
+                }
+            }
+        }
+
+        if !improved {
+            return order;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    fn random_costs(n: usize, next: &mut impl FnMut() -> u64) -> Vec<Vec<Cost>> {
+        (0..n)
+            .map(|a| (0..n).map(|b| if a == b { 0.0 } else { (next() % 50 + 1) as Cost }).collect())
+            .collect()
+    }
+
+    fn brute_force(costs: &[Vec<Cost>]) -> Cost {
+        let n = costs.len();
+
+        (1..n - 1)
+            .permutations(n - 2)
+            .map(|middle| {
+                let mut order = vec![0];
+                order.extend(middle);
+                order.push(n - 1);
+                get_order_cost(costs, &order)
+            })
+            .min_by(|l, r| l.total_cmp(r))
+            .unwrap()
+    }
+
+    fn xorshift(mut seed: u64) -> impl FnMut() -> u64 {
+        move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        }
+    }
+
+    fn assert_valid_order(order: &[usize], n: usize) {
+        assert_eq!(order.first(), Some(&0));
+        assert_eq!(order.last(), Some(&(n - 1)));
+        assert_eq!(order.iter().copied().sorted().collect_vec(), (0..n).collect_vec());
+    }
+
+    #[test]
+    fn held_karp_matches_brute_force() {
+        let mut next = xorshift(0x9e3779b9);
+
+        for n in 4..=8 {
+            for _ in 0..20 {
+                let costs = random_costs(n, &mut next);
+
+                let order = best_order(&costs);
+
+                assert_valid_order(&order, n);
+                assert_eq!(get_order_cost(&costs, &order), brute_force(&costs));
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_start_and_end_fixed() {
+        // 0 -> 2 -> 1 -> 3 is the only order made entirely of cheap legs
+        let costs = vec![
+            vec![0.0, 9.0, 1.0, 9.0],
+            vec![9.0, 0.0, 9.0, 1.0],
+            vec![9.0, 1.0, 0.0, 9.0],
+            vec![9.0, 9.0, 9.0, 0.0],
+        ];
+
+        assert_eq!(best_order(&costs), vec![0, 2, 1, 3]);
+    }
+
+    #[test]
+    fn heuristic_returns_a_locally_optimal_order() {
+        let mut next = xorshift(0xdeadbeef);
+
+        let n = EXACT_WAYPOINT_LIMIT + 10;
+        let costs = random_costs(n, &mut next);
+
+        let order = best_order(&costs);
+
+        assert_valid_order(&order, n);
+        assert!(get_order_cost(&costs, &order) <= get_order_cost(&costs, &nearest_neighbour(&costs)));
+        assert_eq!(improve(&costs, order.clone()), order);
+    }
+
+    #[test]
+    fn heuristic_solves_a_line() {
+        // waypoints on a line, given out of order; the only sensible route walks straight along it
+        let positions = [0.0, 7.0, 3.0, 9.0, 1.0, 5.0, 2.0, 8.0, 4.0, 6.0, 10.0];
+
+        let costs = positions.iter()
+            .map(|a: &f64| positions.iter().map(|b| (a - b).abs()).collect())
+            .collect_vec();
+
+        let order = improve(&costs, (0..positions.len()).collect());
+
+        assert_eq!(get_order_cost(&costs, &order), 10.0);
+    }
+}
@@ -0,0 +1,558 @@
+//! A full-screen view of a route that's planned again whenever the waypoints or filters change.
+//!
+//! The route's jumps are coloured by security, the loaded wormholes and ansiblexes are listed beside it, and a search
+//! box adds waypoints by name.
+
+use std::{collections::HashSet, time::SystemTime};
+
+use itertools::Itertools;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+
+use crate::{
+    find_system_by_id,
+    overlays::OverlayUniverse,
+    route::{PlannedRoute, RoutePreference, RouteRequest},
+    systems, Jump, SystemId, SystemSecurity,
+};
+
+/// How many search results are shown.
+const MAX_MATCHES: usize = 8;
+
+const PREFERENCES: [RoutePreference; 3] = [RoutePreference::Shorter, RoutePreference::Safer, RoutePreference::LessSecure];
+
+pub fn get_security_color(security: SystemSecurity) -> Color {
+    match security {
+        SystemSecurity::Highsec => Color::Green,
+        SystemSecurity::Lowsec => Color::Yellow,
+        SystemSecurity::Nullsec => Color::Red,
+        SystemSecurity::Jspace => Color::Magenta,
+        SystemSecurity::Special => Color::Cyan,
+    }
+}
+
+fn get_preference_name(preference: RoutePreference) -> &'static str {
+    match preference {
+        RoutePreference::Shorter => "shorter",
+        RoutePreference::Safer => "safer",
+        RoutePreference::LessSecure => "less-secure",
+    }
+}
+
+/// A system's name, in the colour of its security.
+fn system_span(id: SystemId) -> Span<'static> {
+    let sys = find_system_by_id(id);
+
+    Span::styled(sys.name, get_security_color(sys.get_security_class()))
+}
+
+/// The panels that can be scrolled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    Route,
+    Wormholes,
+    Ansiblexes,
+}
+
+impl Panel {
+    const ALL: [Panel; 3] = [Panel::Route, Panel::Wormholes, Panel::Ansiblexes];
+
+    fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+}
+
+/// Everything on screen, and the route it shows.
+pub struct RouteView {
+    overlays: OverlayUniverse,
+    /// The waypoints and options the route is planned from
+    request: RouteRequest,
+    /// The route, or why there isn't one
+    planned: Result<PlannedRoute, String>,
+    /// What's typed in the search box
+    search: String,
+    searching: bool,
+    /// The search result that Enter adds
+    selected: usize,
+    focus: Panel,
+    /// How far each panel is scrolled, in lines
+    scroll: [u16; 3],
+    quit: bool,
+}
+
+impl RouteView {
+    pub fn new(overlays: OverlayUniverse, request: RouteRequest) -> Self {
+        let mut view = Self {
+            overlays,
+            request,
+            planned: Err(String::new()),
+            search: String::new(),
+            searching: false,
+            selected: 0,
+            focus: Panel::Route,
+            scroll: [0; 3],
+            quit: false,
+        };
+
+        view.replan();
+
+        view
+    }
+
+    pub fn get_request(&self) -> &RouteRequest {
+        &self.request
+    }
+
+    pub fn get_route(&self) -> Option<&PlannedRoute> {
+        self.planned.as_ref().ok()
+    }
+
+    fn replan(&mut self) {
+        self.scroll[Panel::Route as usize] = 0;
+
+        // the view can stay open for hours, so holes that expired since the last route are left out of this one
+        self.request.filter.wormholes.now = SystemTime::now();
+
+        self.planned = if self.request.waypoints.len() < 2 {
+            Err("Press / to add 2 or more waypoints".to_owned())
+        } else {
+            self.request.plan(self.overlays.get_universe()).map_err(|e| format!("{e:#}"))
+        };
+    }
+
+    /// Systems whose names start with the search, then systems whose names contain it.
+    fn get_matches(&self) -> Vec<SystemId> {
+        let search = self.search.trim().to_lowercase();
+
+        if search.is_empty() {
+            return Vec::new();
+        }
+
+        let all = systems::get_systems();
+
+        let starts = all.iter().filter(|sys| sys.name.to_lowercase().starts_with(&search));
+        let contains = all.iter().filter(|sys| !sys.name.to_lowercase().starts_with(&search) && sys.name.to_lowercase().contains(&search));
+
+        starts.sorted_by_key(|sys| sys.name)
+            .chain(contains.sorted_by_key(|sys| sys.name))
+            .map(|sys| sys.id)
+            .take(MAX_MATCHES)
+            .collect()
+    }
+
+    /// Updates the view for one key press.
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+
+        if self.searching {
+            self.handle_search_key(key.code);
+            return;
+        }
+
+        let filter = &mut self.request.filter;
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char(c @ '1'..='5') => {
+                let security = SystemSecurity::ALL[c as usize - '1' as usize];
+
+                if filter.invalid_securities.contains(&security) {
+                    filter.invalid_securities.retain(|s| *s != security);
+                } else {
+                    filter.invalid_securities.push(security);
+                }
+
+                self.replan();
+            },
+            KeyCode::Char('f') => {
+                filter.strict = !filter.strict;
+                self.replan();
+            },
+            KeyCode::Char('p') => {
+                let preference = &mut self.request.costs.preference;
+                let next = PREFERENCES.iter().position(|p| p == preference).map_or(0, |i| (i + 1) % PREFERENCES.len());
+
+                *preference = PREFERENCES[next];
+                self.replan();
+            },
+            KeyCode::Char('e') => {
+                self.request.exact = !self.request.exact;
+                self.replan();
+            },
+            KeyCode::Char('/') | KeyCode::Char('a') => self.searching = true,
+            KeyCode::Backspace => {
+                self.request.waypoints.pop();
+                self.replan();
+            },
+            KeyCode::Char('c') => {
+                self.request.waypoints.clear();
+                self.replan();
+            },
+            KeyCode::Tab => self.focus = self.focus.next(),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll_by(-1),
+            KeyCode::PageDown => self.scroll_by(10),
+            KeyCode::PageUp => self.scroll_by(-10),
+            _ => {},
+        }
+    }
+
+    fn handle_search_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Esc => {
+                self.searching = false;
+                self.search.clear();
+            },
+            KeyCode::Enter => {
+                if let Some(id) = self.get_matches().get(self.selected) {
+                    self.request.waypoints.push(*id);
+                    self.replan();
+                }
+
+                self.search.clear();
+                self.selected = 0;
+            },
+            KeyCode::Backspace => {
+                self.search.pop();
+                self.selected = 0;
+            },
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.get_matches().len().saturating_sub(1)),
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char(c) => {
+                self.search.push(c);
+                self.selected = 0;
+            },
+            _ => {},
+        }
+    }
+
+    fn scroll_by(&mut self, lines: i32) {
+        let scroll = &mut self.scroll[self.focus as usize];
+
+        *scroll = scroll.saturating_add_signed(lines as i16);
+    }
+
+    /// The route, one line per jump, with a header for each leg.
+    fn get_route_lines(&self) -> Vec<Line<'static>> {
+        let planned = match &self.planned {
+            Ok(planned) => planned,
+            Err(reason) => return vec![Line::from(reason.clone()).dark_gray()],
+        };
+
+        let mut lines = Vec::new();
+
+        for leg in &planned.legs {
+            let Some(jumps) = &leg.jumps else {
+                lines.push(Line::from(vec![system_span(leg.start), " to ".into(), system_span(leg.end), ": no route".into()]).red());
+                continue;
+            };
+
+            lines.push(Line::from(vec![
+                system_span(leg.start),
+                " to ".into(),
+                system_span(leg.end),
+                format!(": {} jumps, cost {:.2}", jumps.len(), leg.cost).into(),
+            ]).bold());
+
+            for (to, from, via) in jumps {
+                let sys = find_system_by_id(*to);
+                let color = get_security_color(sys.get_security_class());
+
+                let mut spans = vec![
+                    Span::styled(format!("  {:5.2} ", sys.security), color),
+                    Span::styled(sys.name, color),
+                    Span::raw(format!("  {}, via {}", sys.region, via.name())),
+                ];
+
+                let extra = self.overlays.get_universe().get_extra_jump(*from, *to, *via);
+
+                if let Some(info) = extra.and_then(|j| j.wormhole.as_ref()) {
+                    let now = self.request.filter.wormholes.now;
+
+                    spans.push(Span::raw(format!(", {}", info.describe(now))));
+
+                    if !info.get_risks(now).is_empty() {
+                        spans.push(Span::styled(format!("  ({})", info.get_risks(now).join(", ")), Color::Red));
+                    }
+                }
+
+                lines.push(Line::from(spans));
+            }
+
+            if let Some(detour) = &leg.detour {
+                lines.push(Line::from(format!("  Detour: {}", detour.describe())).dark_gray());
+            }
+
+            lines.push(Line::default());
+        }
+
+        if planned.is_valid() {
+            lines.push(Line::from(format!("Total: {} jumps, cost {:.2}", planned.get_jump_count(), planned.get_cost())).bold());
+        }
+
+        lines
+    }
+
+    /// Every loaded jump of one kind, once per pair of systems.
+    fn get_connection_lines(&self, kind: Jump) -> Vec<Line<'static>> {
+        let mut seen = HashSet::new();
+        let now = self.request.filter.wormholes.now;
+
+        let lines = self.overlays.get_universe().get_extra_jumps()
+            .filter(|(_, j)| j.via == kind)
+            .filter_map(|(from, j)| {
+                let to = systems::get_systems()[j.to as usize].id;
+
+                if !seen.insert((from.min(to), from.max(to))) {
+                    return None;
+                }
+
+                let mut spans = vec![system_span(from), " - ".into(), system_span(to)];
+
+                if let Some(info) = &j.wormhole {
+                    spans.push(Span::raw(format!("  {}", info.describe(now))).dark_gray());
+                }
+
+                if let Some(note) = &j.note {
+                    spans.push(Span::raw(format!("  \"{note}\"")).dark_gray());
+                }
+
+                Some(Line::from(spans))
+            })
+            .collect_vec();
+
+        if lines.is_empty() {
+            return vec![Line::from("none loaded").dark_gray()];
+        }
+
+        lines
+    }
+
+    fn get_filter_line(&self) -> Line<'static> {
+        let filter = &self.request.filter;
+
+        let mut spans = Vec::new();
+
+        for (i, security) in SystemSecurity::ALL.into_iter().enumerate() {
+            let style = if filter.invalid_securities.contains(&security) {
+                Style::new().fg(Color::DarkGray).add_modifier(Modifier::CROSSED_OUT)
+            } else {
+                Style::new().fg(get_security_color(security))
+            };
+
+            spans.push(Span::raw(format!("[{}] ", i + 1)));
+            spans.push(Span::styled(security.name(), style));
+            spans.push(Span::raw("  "));
+        }
+
+        spans.push(Span::raw(format!("[p] prefer {}  ", get_preference_name(self.request.costs.preference))));
+        spans.push(Span::raw(format!("[f] {}  ", if filter.strict { "never enter filtered" } else { "leave filtered asap" })));
+        spans.push(Span::raw(format!("[e] {}", if self.request.exact { "exact order" } else { "best order" })));
+
+        Line::from(spans)
+    }
+
+    fn get_help_line(&self) -> Line<'static> {
+        let help = if self.searching {
+            "type a system, up/down to pick, enter to add it, esc to stop adding"
+        } else {
+            "/ add waypoint  backspace remove last  c clear  tab switch panel  up/down scroll  q quit"
+        };
+
+        Line::from(help).dark_gray()
+    }
+
+    fn get_panel_block(&self, panel: Panel, title: &str) -> Block<'static> {
+        let block = Block::bordered().title(title.to_owned());
+
+        if self.focus == panel && !self.searching {
+            block.border_style(Style::new().fg(Color::Cyan))
+        } else {
+            block
+        }
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let [waypoints_area, search_area, main_area, filters_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(4),
+        ]).areas(frame.area());
+
+        let waypoints = Itertools::intersperse(self.request.waypoints.iter().map(|id| system_span(*id)), Span::raw(" -> ")).collect_vec();
+
+        frame.render_widget(Paragraph::new(Line::from(waypoints)).block(Block::bordered().title("Waypoints")), waypoints_area);
+
+        let search_block = Block::bordered().title("Add waypoint (/)");
+
+        let search_block = if self.searching { search_block.border_style(Style::new().fg(Color::Cyan)) } else { search_block };
+
+        frame.render_widget(Paragraph::new(self.search.clone()).block(search_block), search_area);
+
+        if self.searching {
+            frame.set_cursor_position((search_area.x + 1 + self.search.chars().count() as u16, search_area.y + 1));
+        }
+
+        let [route_area, side_area] = Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)]).areas(main_area);
+
+        let route_area = self.draw_matches(frame, route_area);
+
+        let route = Paragraph::new(self.get_route_lines())
+            .block(self.get_panel_block(Panel::Route, "Route"))
+            .scroll((self.scroll[Panel::Route as usize], 0));
+
+        frame.render_widget(route, route_area);
+
+        let [wormholes_area, ansiblexes_area] = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(side_area);
+
+        for (panel, kind, title, area) in [(Panel::Wormholes, Jump::Wormhole, "Wormholes", wormholes_area), (Panel::Ansiblexes, Jump::Ansiblex, "Ansiblexes", ansiblexes_area)] {
+            let lines = Paragraph::new(self.get_connection_lines(kind))
+                .block(self.get_panel_block(panel, title))
+                .scroll((self.scroll[panel as usize], 0));
+
+            frame.render_widget(lines, area);
+        }
+
+        frame.render_widget(Paragraph::new(vec![self.get_filter_line(), self.get_help_line()]).block(Block::bordered().title("Filters")), filters_area);
+    }
+
+    /// Draws the search results at the top of `area` while searching, and returns what's left of it.
+    fn draw_matches(&self, frame: &mut Frame, area: Rect) -> Rect {
+        let matches = self.get_matches();
+
+        if !self.searching || matches.is_empty() {
+            return area;
+        }
+
+        let [matches_area, rest] = Layout::vertical([Constraint::Length(matches.len() as u16 + 2), Constraint::Min(0)]).areas(area);
+
+        let items = matches.iter()
+            .map(|id| {
+                let sys = find_system_by_id(*id);
+
+                ListItem::new(Line::from(vec![system_span(*id), Span::raw(format!("  {:.2}, {}", sys.security, sys.region))]))
+            })
+            .collect_vec();
+
+        let list = List::new(items)
+            .block(Block::bordered().title("Systems"))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        frame.render_stateful_widget(list, matches_area, &mut ListState::default().with_selected(Some(self.selected)));
+
+        rest
+    }
+
+    /// Draws and handles keys until the view is closed.
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if let Event::Key(key) = event::read()? {
+                self.handle_key(key);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Takes over the terminal until the view is closed.
+pub fn run(view: &mut RouteView) -> anyhow::Result<()> {
+    let mut terminal = ratatui::init();
+
+    let result = view.run(&mut terminal);
+
+    ratatui::restore();
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::*;
+    use crate::{diagnostics::ParseMode, find_system_by_name, overlays::OverlayFiles, route::FILTERED_JUMP_PENALTY, universe::Universe};
+
+    fn view(waypoints: &[&str]) -> RouteView {
+        let overlays = OverlayUniverse::new(Universe::stargates(), OverlayFiles::default(), ParseMode::Strict).unwrap();
+        let waypoints = waypoints.iter().map(|name| find_system_by_name(name).unwrap()).collect();
+
+        RouteView::new(overlays, RouteRequest::new(waypoints))
+    }
+
+    fn press(view: &mut RouteView, keys: &str) {
+        for c in keys.chars() {
+            let code = match c {
+                '\n' => KeyCode::Enter,
+                '\x08' => KeyCode::Backspace,
+                '\x1b' => KeyCode::Esc,
+                c => KeyCode::Char(c),
+            };
+
+            view.handle_key(KeyEvent::new(code, KeyModifiers::NONE));
+        }
+    }
+
+    #[test]
+    fn search_adds_waypoints() {
+        let mut view = view(&[]);
+
+        assert!(view.get_route().is_none());
+
+        press(&mut view, "/jita\nperim\n\x1b");
+
+        assert_eq!(view.get_route().unwrap().get_jump_count(), 1);
+
+        press(&mut view, "\x08");
+
+        assert_eq!(view.get_request().waypoints, vec![find_system_by_name("Jita").unwrap()]);
+        assert!(view.get_route().is_none());
+    }
+
+    #[test]
+    fn toggles_replan_the_route() {
+        let mut view = view(&["Jita", "Perimeter"]);
+
+        press(&mut view, "1");
+
+        // both ends are filtered out, so the jump is only penalized
+        assert_eq!(view.get_request().filter.invalid_securities, vec![SystemSecurity::Highsec]);
+        assert!(view.get_route().unwrap().get_cost() > FILTERED_JUMP_PENALTY);
+
+        press(&mut view, "1p");
+
+        assert!(view.get_request().filter.invalid_securities.is_empty());
+        assert_eq!(view.get_request().costs.preference, RoutePreference::Safer);
+        assert_eq!(view.get_route().unwrap().get_cost(), 1.0);
+    }
+
+    #[test]
+    fn draws_the_route() {
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        let view = view(&["Jita", "Perimeter"]);
+
+        terminal.draw(|frame| view.draw(frame)).unwrap();
+
+        let screen = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect::<String>();
+
+        assert!(screen.contains("Jita -> Perimeter"));
+        assert!(screen.contains("Jita to Perimeter: 1 jumps, cost 1.00"));
+        assert!(screen.contains("none loaded"));
+    }
+}
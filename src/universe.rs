@@ -0,0 +1,665 @@
+use std::{borrow::Cow, time::SystemTime};
+
+use itertools::Itertools;
+
+use crate::{
+    connections::{self, ConnectionFormat},
+    diagnostics::{self, Diagnostic, LineError, ParseErrors, ParseMode},
+    chain::{self, MapperFormat},
+    eve_scout, find_system_by_name, jump_drive,
+    search::Cost,
+    systems::{self, SystemId, SystemIndex},
+    wormhole::WormholeInfo,
+    Jump,
+};
+
+/// A compressed sparse row graph over system indices: the edges out of system `i` lead to
+/// `targets[offsets[i]..offsets[i + 1]]`.
+#[derive(Debug, Clone, Default)]
+pub struct Csr {
+    offsets: Cow<'static, [u32]>,
+    targets: Cow<'static, [SystemIndex]>,
+}
+
+impl Csr {
+    /// The stargate network generated at build time, or loaded with `systems::install`.
+    pub fn stargates() -> Self {
+        let (offsets, targets) = systems::get_gate_graph();
+
+        Self {
+            offsets: Cow::Borrowed(offsets),
+            targets: Cow::Borrowed(targets),
+        }
+    }
+
+    /// Builds a graph over `len` systems from `(from, to)` pairs. Duplicates are ignored.
+    pub fn from_edges(len: usize, mut edges: Vec<(SystemIndex, SystemIndex)>) -> Self {
+        edges.sort_unstable();
+        edges.dedup();
+
+        let mut offsets = vec![0u32; len + 1];
+
+        for (from, _) in &edges {
+            offsets[*from as usize + 1] += 1;
+        }
+
+        for i in 0..len {
+            offsets[i + 1] += offsets[i];
+        }
+
+        Self {
+            offsets: Cow::Owned(offsets),
+            targets: Cow::Owned(edges.into_iter().map(|(_, to)| to).collect()),
+        }
+    }
+
+    /// The systems `from` has an edge to.
+    pub fn get_edges(&self, from: SystemIndex) -> &[SystemIndex] {
+        let from = from as usize;
+
+        if from + 1 >= self.offsets.len() {
+            return &[];
+        }
+
+        &self.targets[self.offsets[from] as usize..self.offsets[from + 1] as usize]
+    }
+}
+
+/// A jump loaded on top of the base graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtraJump {
+    pub to: SystemIndex,
+    pub via: Jump,
+    /// Replaces the cost of `via`, for this jump only
+    pub cost: Option<Cost>,
+    pub note: Option<String>,
+    /// Set for wormholes that came with more than their two ends, like a size or an expiry time
+    pub wormhole: Option<WormholeInfo>,
+    /// When the jump stops existing, for connections loaded with an expiry time whatever their kind
+    pub expires: Option<SystemTime>,
+}
+
+impl ExtraJump {
+    /// A jump to `to` with nothing else known about it. Panics if `to` doesn't exist.
+    pub fn new(to: SystemId, via: Jump) -> Self {
+        Self {
+            to: systems::get_system_index(to).expect("jump to a system that doesn't exist"),
+            via,
+            cost: None,
+            note: None,
+            wormhole: None,
+            expires: None,
+        }
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+/// Every jump that can be taken, by the system it starts in.
+///
+/// The jumps every route can use (stargates, or jump drive hops) are kept in a [`Csr`], and anything loaded on top of
+/// them, like wormholes and ansiblexes, in a small overlay.
+#[derive(Debug, Clone)]
+pub struct Universe {
+    base: Csr,
+    /// What every jump in `base` is
+    base_via: Jump,
+    /// Extra jumps, by the index of the system they start in
+    overlay: Vec<Vec<ExtraJump>>,
+    /// Jumps that can't be taken, whatever kind they are, by the index of the system they start in
+    removed: Vec<Vec<SystemIndex>>,
+}
+
+impl Default for Universe {
+    fn default() -> Self {
+        Self {
+            base: Csr::default(),
+            base_via: Jump::Gate,
+            overlay: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+
+impl Universe {
+    /// Every stargate in New Eden.
+    pub fn stargates() -> Self {
+        Self {
+            base: Csr::stargates(),
+            base_via: Jump::Gate,
+            overlay: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// A jump between every pair of systems within `range` light years, when the destination can have a cyno.
+    pub fn jump_drive(range: f64) -> Self {
+        let all = systems::get_systems();
+
+        let mut systems = (0..all.len() as SystemIndex).filter(|idx| jump_drive::can_jump_from(&all[*idx as usize])).collect_vec();
+
+        // sweep along x, so that only systems that could possibly be in range are compared
+        systems.sort_by(|l, r| all[*l as usize].position[0].total_cmp(&all[*r as usize].position[0]));
+
+        let range_m = range * jump_drive::METERS_PER_LY;
+
+        let mut edges = Vec::new();
+
+        for (i, a_idx) in systems.iter().enumerate() {
+            let a = &all[*a_idx as usize];
+
+            for b_idx in &systems[i + 1..] {
+                let b = &all[*b_idx as usize];
+
+                if b.position[0] - a.position[0] > range_m {
+                    break;
+                }
+
+                if jump_drive::get_distance_ly(a, b) > range {
+                    continue;
+                }
+
+                if jump_drive::can_jump_to(b) {
+                    edges.push((*a_idx, *b_idx));
+                }
+
+                if jump_drive::can_jump_to(a) {
+                    edges.push((*b_idx, *a_idx));
+                }
+            }
+        }
+
+        Self {
+            base: Csr::from_edges(all.len(), edges),
+            base_via: Jump::Cyno,
+            overlay: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+
+    /// Adds a jump on top of the base graph. Panics if either system doesn't exist.
+    pub fn add_jump(&mut self, from: SystemId, to: SystemId, via: Jump) {
+        self.add_extra_jump(from, ExtraJump::new(to, via));
+    }
+
+    /// Adds a jump with its own cost, note, expiry, or wormhole info. A jump that's already there keeps what it has,
+    /// and gets whatever more `jump` knows about it, like a wormhole's size from a second source. Panics if `from`
+    /// doesn't exist.
+    pub fn add_extra_jump(&mut self, from: SystemId, jump: ExtraJump) {
+        let from = systems::get_system_index(from).expect("jump from a system that doesn't exist");
+
+        if jump.via == self.base_via && self.base.get_edges(from).contains(&jump.to) {
+            return;
+        }
+
+        if self.overlay.len() <= from as usize {
+            self.overlay.resize_with(from as usize + 1, Vec::new);
+        }
+
+        let jumps = &mut self.overlay[from as usize];
+
+        let Some(existing) = jumps.iter_mut().find(|j| j.to == jump.to && j.via == jump.via) else {
+            jumps.push(jump);
+            return;
+        };
+
+        existing.cost = existing.cost.or(jump.cost);
+        existing.note = existing.note.take().or(jump.note);
+        existing.expires = existing.expires.into_iter().chain(jump.expires).min();
+
+        match (&mut existing.wormhole, jump.wormhole) {
+            (Some(info), Some(more)) => info.merge(more),
+            (info, more) => *info = info.take().or(more),
+        }
+    }
+
+    /// Adds a jump in both directions.
+    pub fn add_connection(&mut self, a: SystemId, b: SystemId, via: Jump) {
+        self.add_jump(a, b, via);
+        self.add_jump(b, a, via);
+    }
+
+    /// Adds a wormhole in both directions, keeping whatever is known about it.
+    pub fn add_wormhole(&mut self, a: SystemId, b: SystemId, info: WormholeInfo) {
+        self.add_wormhole_ends(a, b, info.clone(), info);
+    }
+
+    /// Adds a wormhole in both directions, when each end has its own signature.
+    pub fn add_wormhole_ends(&mut self, a: SystemId, b: SystemId, a_to_b: WormholeInfo, b_to_a: WormholeInfo) {
+        self.add_extra_jump(a, ExtraJump { wormhole: a_to_b.is_known().then_some(a_to_b), ..ExtraJump::new(b, Jump::Wormhole) });
+        self.add_extra_jump(b, ExtraJump { wormhole: b_to_a.is_known().then_some(b_to_a), ..ExtraJump::new(a, Jump::Wormhole) });
+    }
+
+    /// Stops routes from jumping from `from` to `to`, however the two are connected. The other direction is left
+    /// alone. Panics if either system doesn't exist.
+    pub fn remove_jump(&mut self, from: SystemId, to: SystemId) {
+        let from = systems::get_system_index(from).expect("jump from a system that doesn't exist");
+        let to = systems::get_system_index(to).expect("jump to a system that doesn't exist");
+
+        if self.removed.len() <= from as usize {
+            self.removed.resize_with(from as usize + 1, Vec::new);
+        }
+
+        if !self.removed[from as usize].contains(&to) {
+            self.removed[from as usize].push(to);
+        }
+    }
+
+    pub fn is_removed(&self, from: SystemIndex, to: SystemIndex) -> bool {
+        self.removed.get(from as usize).is_some_and(|removed| removed.contains(&to))
+    }
+
+    pub fn has_removed_jumps(&self) -> bool {
+        self.removed.iter().any(|removed| !removed.is_empty())
+    }
+
+    /// This universe with every removed jump put back, to see what routes would look like without them.
+    pub fn without_removals(&self) -> Self {
+        Self {
+            removed: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    /// The jumps out of the system at `idx`, as `(destination index, via, extra jump)`, where the extra jump is set for
+    /// anything loaded on top of the base graph. Removed jumps are left out.
+    pub fn get_jumps_from(&self, idx: SystemIndex) -> impl Iterator<Item = (SystemIndex, Jump, Option<&ExtraJump>)> + '_ {
+        let base = self.base.get_edges(idx).iter().map(|to| (*to, self.base_via, None));
+        let overlay = self.overlay.get(idx as usize).into_iter().flatten().map(|j| (j.to, j.via, Some(j)));
+
+        let removed = self.removed.get(idx as usize).map(|r| r.as_slice()).unwrap_or_default();
+
+        base.chain(overlay).filter(move |(to, _, _)| !removed.contains(to))
+    }
+
+    /// The extra jump from `from` to `to` via `via`, if one was added.
+    pub fn get_extra_jump(&self, from: SystemId, to: SystemId, via: Jump) -> Option<&ExtraJump> {
+        let from = systems::get_system_index(from)?;
+        let to = systems::get_system_index(to)?;
+
+        self.overlay.get(from as usize)?.iter().find(|j| j.to == to && j.via == via)
+    }
+
+    /// Every jump loaded on top of the base graph, with the system it starts in.
+    pub fn get_extra_jumps(&self) -> impl Iterator<Item = (SystemId, &ExtraJump)> + '_ {
+        let all = systems::get_systems();
+
+        self.overlay.iter()
+            .enumerate()
+            .flat_map(move |(idx, jumps)| jumps.iter().map(move |j| (all[idx].id, j)))
+    }
+
+    /// The jumps out of `sys`, as `(destination, via)`.
+    pub fn get_jumps(&self, sys: SystemId) -> impl Iterator<Item = (SystemId, Jump)> + '_ {
+        let all = systems::get_systems();
+
+        systems::get_system_index(sys)
+            .into_iter()
+            .flat_map(|idx| self.get_jumps_from(idx))
+            .map(move |(to, via, _)| (all[to as usize].id, via))
+    }
+
+    /// Adds both directions of every wormhole in a file of copy+pasted bookmarks. Duplicates are ignored.
+    ///
+    /// Tags after the systems in a bookmark's name, like `ABC-123 Jita -&gt; Thera L EOL crit`, say what size the hole
+    /// is, whether it's end of life, and how much mass it has left. Jumps out of the system the bookmark is in remember
+    /// its label, so routes can say which bookmark to warp to, and jumps back keep it as the far side's bookmark.
+    ///
+    /// `file` labels diagnostics, and is kept as the source of each wormhole. Returns the lines that were skipped in
+    /// lenient mode.
+    pub fn load_wormholes(&mut self, file: &str, content: &str, mode: ParseMode) -> Result<Vec<Diagnostic>, ParseErrors> {
+        let (connections, warnings) = diagnostics::parse_lines(file, content, mode, try_parse_wh_line)?;
+
+        for (bookmarked, other, info) in connections {
+            let info = WormholeInfo { source: Some(file.to_owned()), ..info };
+
+            let far_side = WormholeInfo { bookmark: None, far_bookmark: info.bookmark.clone(), ..info.clone() };
+
+            self.add_wormhole_ends(bookmarked, other, info, far_side);
+        }
+
+        Ok(warnings)
+    }
+
+    /// Adds both directions of every ansiblex in an SMT-compatible file.
+    ///
+    /// `file` is only used to label diagnostics. Returns the lines that were skipped in lenient mode.
+    pub fn load_ansiblexes(&mut self, file: &str, content: &str, mode: ParseMode) -> Result<Vec<Diagnostic>, ParseErrors> {
+        let (connections, warnings) = diagnostics::parse_lines(file, content, mode, try_parse_ansi_line)?;
+
+        for (system_id, dst_id) in connections {
+            self.add_connection(system_id, dst_id, Jump::Ansiblex);
+        }
+
+        Ok(warnings)
+    }
+
+    /// Removes every jump in a file with one `From -> To` per line.
+    ///
+    /// `file` is only used to label diagnostics. Returns the lines that were skipped in lenient mode.
+    pub fn load_removed_jumps(&mut self, file: &str, content: &str, mode: ParseMode) -> Result<Vec<Diagnostic>, ParseErrors> {
+        let (jumps, warnings) = diagnostics::parse_lines(file, content, mode, try_parse_removed_line)?;
+
+        for (from, to) in jumps {
+            self.remove_jump(from, to);
+        }
+
+        Ok(warnings)
+    }
+
+    /// Adds both directions of every wormhole in a saved Eve-Scout signature list, keeping the signature at each end.
+    ///
+    /// `file` labels diagnostics, and is kept as the source of each wormhole. Returns the entries that were skipped in
+    /// lenient mode.
+    pub fn load_eve_scout(&mut self, file: &str, content: &str, mode: ParseMode, now: SystemTime) -> Result<Vec<Diagnostic>, ParseErrors> {
+        let (wormholes, warnings) = eve_scout::parse_signatures(file, content, mode, now)?;
+
+        let source = || Some(file.to_owned());
+
+        for hole in wormholes {
+            self.add_wormhole_ends(
+                hole.out_system,
+                hole.in_system,
+                WormholeInfo { source: source(), ..hole.outward },
+                WormholeInfo { source: source(), ..hole.inward },
+            );
+        }
+
+        Ok(warnings)
+    }
+
+    /// Adds both directions of every wormhole in a chain exported from Tripwire, Pathfinder, or Wanderer that hasn't
+    /// expired by `now`.
+    ///
+    /// `file` labels diagnostics, and is kept as the source of each wormhole. Returns the format it was read as, and
+    /// the entries that were skipped in lenient mode.
+    pub fn load_chain(&mut self, file: &str, content: &str, mode: ParseMode, now: SystemTime) -> Result<(MapperFormat, Vec<Diagnostic>), ParseErrors> {
+        let (format, wormholes, warnings) = chain::parse_chain(file, content, mode, now)?;
+
+        let source = || Some(file.to_owned());
+
+        for hole in wormholes {
+            self.add_wormhole_ends(
+                hole.a,
+                hole.b,
+                WormholeInfo { source: source(), ..hole.a_to_b },
+                WormholeInfo { source: source(), ..hole.b_to_a },
+            );
+        }
+
+        Ok((format, warnings))
+    }
+
+    /// Adds every connection in a connections file that hasn't expired by `now`.
+    ///
+    /// `file` labels diagnostics, and is kept as the source of any wormhole info. Returns the entries that were skipped in lenient mode.
+    pub fn load_connections(&mut self, file: &str, content: &str, format: ConnectionFormat, mode: ParseMode, now: SystemTime) -> Result<Vec<Diagnostic>, ParseErrors> {
+        let (connections, warnings) = connections::parse_connections(file, content, format, mode, now)?;
+
+        for c in connections {
+            let jump = |to| ExtraJump {
+                cost: c.cost,
+                note: c.note.clone(),
+                wormhole: c.get_wormhole_info().map(|info| WormholeInfo { source: Some(file.to_owned()), ..info }),
+                expires: c.expires,
+                ..ExtraJump::new(to, c.kind)
+            };
+
+            self.add_extra_jump(c.from, jump(c.to));
+
+            if !c.one_way {
+                self.add_extra_jump(c.to, jump(c.from));
+            }
+        }
+
+        Ok(warnings)
+    }
+}
+
+/// Looks up a system named by a slice of `line`, pointing the error at the name.
+fn find_system_in_line(line: &str, name: &str) -> Result<SystemId, LineError> {
+    find_system_by_name(name).map_err(|e| LineError::at(line, name, e.to_string()))
+}
+
+/// Parses a bookmark line into `(the system the bookmark is in, the other end, what the name says)`.
+fn try_parse_wh_line(line: &str) -> Result<Option<(SystemId, SystemId, WormholeInfo)>, LineError> {
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let cols = line.split('\t').collect::<Vec<_>>();
+
+    if cols.len() < 4 {
+        return Err(LineError::at_end(line, format!("expected at least 4 tab-separated columns, found {}", cols.len())));
+    }
+
+    let bm_name = cols[0]
+        .split(' ')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+    let bm_system = cols[3].trim();
+
+    let (a, b, tags) = if bm_name.contains(&"-&gt;") {
+        if bm_name.len() < 4 || bm_name[2] != "-&gt;" {
+            return Err(LineError::at(line, cols[0], "expected a bookmark name like 'ABC-123 Jita -&gt; Thera'"));
+        }
+
+        (bm_name[1], bm_name[3], &bm_name[4..])
+    } else {
+        if bm_name.len() < 2 {
+            return Err(LineError::at(line, cols[0], "expected a bookmark name like 'ABC-123 K7D-II (NS)'"));
+        }
+
+        if bm_system.is_empty() {
+            return Err(LineError::at(line, cols[3], "expected the system the bookmark is in"));
+        }
+
+        (bm_name[1], bm_system, &bm_name[2..])
+    };
+
+    // anything that isn't a tag, like '(NS)', is just part of the name
+    let mut info = WormholeInfo {
+        bookmark: Some(bm_name[0].to_owned()),
+        ..Default::default()
+    };
+
+    for tag in tags {
+        info.apply_tag(tag);
+    }
+
+    let (a, b) = (find_system_in_line(line, a)?, find_system_in_line(line, b)?);
+
+    // bookmarks are usually in the first system of 'A -&gt; B', but not always
+    if find_system_by_name(bm_system).is_ok_and(|located| located == b) {
+        return Ok(Some((b, a, info)));
+    }
+
+    Ok(Some((a, b, info)))
+}
+
+fn try_parse_ansi_line(line: &str) -> Result<Option<(SystemId, SystemId)>, LineError> {
+    let trimmed = line.trim();
+
+    if trimmed.starts_with("#") || trimmed.is_empty() {
+        return Ok(None);
+    }
+
+    let rest = trimmed.trim_start_matches(['0', '1', '2', '3', '4', '5', '6', '7', '8', '9']).trim_start();
+
+    let (from, to) = rest.split_once("-->").ok_or_else(|| LineError::at(line, rest, "could not find '-->' on line"))?;
+
+    let from = from.trim();
+    let to = to.trim();
+
+    if from.is_empty() {
+        return Err(LineError::at(line, rest, "expected a system before '-->'"));
+    }
+
+    if to.is_empty() {
+        return Err(LineError::at_end(line, "expected a system after '-->'"));
+    }
+
+    Ok(Some((find_system_in_line(line, from)?, find_system_in_line(line, to)?)))
+}
+
+/// Parses a `From -> To` line, ignoring blank lines and `#` comments.
+pub fn try_parse_removed_line(line: &str) -> Result<Option<(SystemId, SystemId)>, LineError> {
+    let trimmed = line.trim();
+
+    if trimmed.starts_with('#') || trimmed.is_empty() {
+        return Ok(None);
+    }
+
+    let (from, to) = trimmed.split_once("->").ok_or_else(|| LineError::at(line, trimmed, "expected 'From -> To'"))?;
+
+    let from = from.trim();
+    let to = to.trim();
+
+    if from.is_empty() {
+        return Err(LineError::at(line, trimmed, "expected a system before '->'"));
+    }
+
+    if to.is_empty() {
+        return Err(LineError::at_end(line, "expected a system after '->'"));
+    }
+
+    Ok(Some((find_system_in_line(line, from)?, find_system_in_line(line, to)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csr_lists_edges_by_source() {
+        let csr = Csr::from_edges(4, vec![(2, 0), (0, 1), (2, 3), (0, 1), (0, 2)]);
+
+        assert_eq!(csr.get_edges(0), &[1, 2]);
+        assert_eq!(csr.get_edges(1), &[] as &[SystemIndex]);
+        assert_eq!(csr.get_edges(2), &[0, 3]);
+        assert_eq!(csr.get_edges(3), &[] as &[SystemIndex]);
+        assert_eq!(csr.get_edges(10), &[] as &[SystemIndex]);
+    }
+
+    #[test]
+    fn overlay_adds_to_the_gates() {
+        let jita = find_system_by_name("Jita").unwrap();
+        let thera = find_system_by_name("Thera").unwrap();
+
+        let mut universe = Universe::stargates();
+        let gates = universe.get_jumps(jita).count();
+
+        universe.add_connection(jita, thera, Jump::Wormhole);
+        universe.add_connection(jita, thera, Jump::Wormhole);
+
+        assert_eq!(universe.get_jumps(jita).count(), gates + 1);
+        assert!(universe.get_jumps(jita).all(|(_, via)| via == Jump::Gate || via == Jump::Wormhole));
+        assert_eq!(universe.get_jumps(thera).collect_vec(), vec![(jita, Jump::Wormhole)]);
+        assert_eq!(universe.get_extra_jumps().map(|(from, j)| (from, j.via)).collect_vec(), vec![(jita, Jump::Wormhole), (thera, Jump::Wormhole)]);
+    }
+
+    #[test]
+    fn a_hole_from_two_sources_keeps_what_both_know() {
+        let jita = find_system_by_name("Jita").unwrap();
+        let thera = find_system_by_name("Thera").unwrap();
+
+        let mut universe = Universe::stargates();
+
+        universe.add_connection(jita, thera, Jump::Wormhole);
+        universe.add_wormhole(jita, thera, WormholeInfo { signature: Some("ABC-123".to_owned()), ..Default::default() });
+        universe.add_wormhole(jita, thera, WormholeInfo { signature: Some("XYZ-789".to_owned()), size: Some(crate::ShipSize::Large), ..Default::default() });
+
+        let info = universe.get_extra_jump(jita, thera, Jump::Wormhole).unwrap().wormhole.clone().unwrap();
+
+        assert_eq!(info, WormholeInfo { signature: Some("ABC-123".to_owned()), size: Some(crate::ShipSize::Large), ..Default::default() });
+        assert_eq!(universe.get_jumps(jita).filter(|(to, _)| *to == thera).count(), 1);
+    }
+
+    #[test]
+    fn bookmark_tags_describe_the_hole() {
+        let (_, _, info) = try_parse_wh_line("ABC-123 Jita -&gt; Thera XL EOL crit\tCoordinate\t0 m\tJita").unwrap().unwrap();
+
+        assert_eq!(info.size, Some(crate::ShipSize::Xl));
+        assert!(info.eol);
+        assert_eq!(info.mass, Some(crate::MassState::Critical));
+
+        let (_, _, info) = try_parse_wh_line("ABC-123 K7D-II (NS)\tCoordinate\t0 m\tJita").unwrap().unwrap();
+
+        assert_eq!(info, WormholeInfo { bookmark: Some("ABC-123".to_owned()), ..Default::default() });
+    }
+
+    #[test]
+    fn bookmarks_are_kept_on_the_side_they_are_in() {
+        let jita = find_system_by_name("Jita").unwrap();
+        let k7d = find_system_by_name("K7D-II").unwrap();
+
+        let mut universe = Universe::stargates();
+
+        universe.load_wormholes("holes.txt", "ABC-123 K7D-II (NS)\tCoordinate\t0 m\tJita\n", ParseMode::Strict).unwrap();
+
+        let there = universe.get_extra_jump(jita, k7d, Jump::Wormhole).and_then(|j| j.wormhole.as_ref()).unwrap();
+        let back = universe.get_extra_jump(k7d, jita, Jump::Wormhole).and_then(|j| j.wormhole.as_ref()).unwrap();
+
+        assert_eq!((there.bookmark.as_deref(), there.far_bookmark.as_deref()), (Some("ABC-123"), None));
+        assert_eq!((back.bookmark.as_deref(), back.far_bookmark.as_deref()), (None, Some("ABC-123")));
+        assert_eq!(back.source.as_deref(), Some("holes.txt"));
+    }
+
+    #[test]
+    fn removed_jumps_only_go_one_way() {
+        let jita = find_system_by_name("Jita").unwrap();
+        let perimeter = find_system_by_name("Perimeter").unwrap();
+
+        let mut universe = Universe::stargates();
+
+        assert!(universe.get_jumps(jita).any(|(to, _)| to == perimeter));
+
+        universe.load_removed_jumps("removed.txt", "# camped\nJita -> Perimeter\n", ParseMode::Strict).unwrap();
+
+        assert!(!universe.get_jumps(jita).any(|(to, _)| to == perimeter));
+        assert!(universe.get_jumps(perimeter).any(|(to, _)| to == jita));
+        assert!(universe.without_removals().get_jumps(jita).any(|(to, _)| to == perimeter));
+
+        assert_eq!(try_parse_removed_line("Jita Perimeter").unwrap_err().column, 1);
+        assert_eq!(try_parse_removed_line("Jita -> ").unwrap_err().column, 9);
+    }
+
+    fn wh_error(line: &str) -> (usize, String) {
+        let err = try_parse_wh_line(line).unwrap_err();
+
+        (err.column, err.reason)
+    }
+
+    fn ansi_error(line: &str) -> (usize, String) {
+        let err = try_parse_ansi_line(line).unwrap_err();
+
+        (err.column, err.reason)
+    }
+
+    #[test]
+    fn short_wormhole_lines_are_errors() {
+        assert_eq!(wh_error("ABC-123 Jita -&gt; Thera\tCoordinate"), (36, "expected at least 4 tab-separated columns, found 2".to_owned()));
+        assert_eq!(wh_error("ABC-123 -&gt;\ta\tb\tJita").0, 1);
+        assert_eq!(wh_error("ABC-123\ta\tb\tJita").0, 1);
+        assert_eq!(wh_error("ABC-123 K7D-II (NS)\ta\tb\t ").0, 25);
+    }
+
+    #[test]
+    fn unknown_systems_point_at_the_name() {
+        assert_eq!(wh_error("ABC-123 Nowhere123 -&gt; Jita\ta\tb\tJita"), (9, "could not find system 'nowhere123'".to_owned()));
+        assert_eq!(ansi_error("1001 Nowhere123 --> Jita").0, 6);
+    }
+
+    #[test]
+    fn bad_ansiblex_lines_are_errors() {
+        assert!(try_parse_ansi_line("# a comment").unwrap().is_none());
+        assert!(try_parse_ansi_line("   ").unwrap().is_none());
+
+        assert_eq!(ansi_error("1001 Jita -> Perimeter"), (6, "could not find '-->' on line".to_owned()));
+        assert_eq!(ansi_error("1001 Jita -->"), (14, "expected a system after '-->'".to_owned()));
+        assert_eq!(ansi_error("1001 --> Jita").0, 6);
+    }
+}
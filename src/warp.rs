@@ -0,0 +1,101 @@
+pub const METERS_PER_AU: f64 = 149_597_870_700.0;
+
+/// Ships drop out of warp once they slow down to this speed, in m/s.
+const WARP_DROPOUT_SPEED: f64 = 100.0;
+
+/// How far a warp to or from something other than a stargate is assumed to be, in AU. Wormhole signatures and
+/// ansiblexes aren't in the SDE, so there's no way to know where they are.
+pub const UNKNOWN_WARP_DISTANCE: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WarpProfile {
+    /// Maximum warp speed, in AU/s
+    pub warp_speed: f64,
+    /// Time to align for warp, in seconds
+    pub align_time: f64,
+    /// Time from landing on a gate to being able to align in the next system (activation, session change, and
+    /// dropping the gate cloak), in seconds
+    pub jump_overhead: f64,
+}
+
+impl WarpProfile {
+    /// The time spent in warp to cover `distance` meters, in seconds.
+    ///
+    /// Ships accelerate at `warp_speed` per second and decelerate at a third of that (capped at 2 AU/s per second),
+    /// so short warps never reach full speed.
+    pub fn get_warp_time(&self, distance: f64) -> f64 {
+        if distance <= 0.0 {
+            return 0.0;
+        }
+
+        let k_accel = self.warp_speed;
+        let k_decel = (self.warp_speed / 3.0).min(2.0);
+
+        let max_speed = self.warp_speed * METERS_PER_AU;
+
+        let accel_distance = max_speed / k_accel;
+        let decel_distance = max_speed / k_decel;
+
+        let (peak_speed, cruise_time) = if distance < accel_distance + decel_distance {
+            (distance * k_accel * k_decel / (k_accel + k_decel), 0.0)
+        } else {
+            (max_speed, (distance - accel_distance - decel_distance) / max_speed)
+        };
+
+        let accel_time = (peak_speed / k_accel).max(1.0).ln() / k_accel;
+        let decel_time = (peak_speed / WARP_DROPOUT_SPEED).max(1.0).ln() / k_decel;
+
+        accel_time + cruise_time + decel_time
+    }
+
+    /// The time to cross a system, from arriving at `entry` to jumping out at `exit`, in seconds.
+    ///
+    /// Positions that aren't known (the start of the route, wormholes, and ansiblexes) are assumed to be
+    /// `UNKNOWN_WARP_DISTANCE` apart.
+    pub fn get_crossing_time(&self, entry: Option<[f64; 3]>, exit: Option<[f64; 3]>) -> f64 {
+        let distance = match (entry, exit) {
+            (Some([x1, y1, z1]), Some([x2, y2, z2])) => ((x2 - x1).powi(2) + (y2 - y1).powi(2) + (z2 - z1).powi(2)).sqrt(),
+            _ => UNKNOWN_WARP_DISTANCE * METERS_PER_AU,
+        };
+
+        self.align_time + self.get_warp_time(distance) + self.jump_overhead
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BATTLESHIP: WarpProfile = WarpProfile { warp_speed: 3.0, align_time: 10.0, jump_overhead: 10.0 };
+
+    #[test]
+    fn long_warps_cruise_at_full_speed() {
+        // 1 AU accelerating, 3 AU decelerating, and 6 AU at 3 AU/s
+        let time = BATTLESHIP.get_warp_time(10.0 * METERS_PER_AU);
+
+        let expected = METERS_PER_AU.ln() / 3.0 + 2.0 + (3.0 * METERS_PER_AU / WARP_DROPOUT_SPEED).ln();
+
+        assert!((time - expected).abs() < 1e-9);
+        assert!((32.0..34.0).contains(&time));
+    }
+
+    #[test]
+    fn warp_time_grows_with_distance() {
+        let distances = [0.0, 1e6, 1e9, METERS_PER_AU, 4.0 * METERS_PER_AU, 40.0 * METERS_PER_AU];
+
+        let times = distances.map(|d| BATTLESHIP.get_warp_time(d));
+
+        assert!(times.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn faster_ships_cross_systems_sooner() {
+        let interceptor = WarpProfile { warp_speed: 8.0, align_time: 2.0, jump_overhead: 10.0 };
+
+        let entry = Some([0.0, 0.0, 0.0]);
+        let exit = Some([20.0 * METERS_PER_AU, 0.0, 0.0]);
+
+        assert!(interceptor.get_crossing_time(entry, exit) < BATTLESHIP.get_crossing_time(entry, exit));
+        assert_eq!(BATTLESHIP.get_crossing_time(None, exit), BATTLESHIP.get_crossing_time(entry, Some([10.0 * METERS_PER_AU, 0.0, 0.0])));
+    }
+}
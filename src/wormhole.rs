@@ -0,0 +1,325 @@
+//! What is known about a wormhole beyond its two ends: the biggest ship it lets through, when it dies, and how much
+//! mass it has left.
+
+use std::time::{Duration, SystemTime};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::fatigue;
+
+/// How long a wormhole has left once it shows as end of life.
+pub const END_OF_LIFE: Duration = Duration::from_secs(4 * 60 * 60);
+
+/// The biggest ships a wormhole lets through.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ShipSize {
+    /// Frigates and destroyers
+    Small,
+    /// Cruisers and battlecruisers
+    Medium,
+    /// Battleships
+    Large,
+    /// Freighters and capitals
+    Xl,
+}
+
+impl ShipSize {
+    pub const ALL: [ShipSize; 4] = [ShipSize::Small, ShipSize::Medium, ShipSize::Large, ShipSize::Xl];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShipSize::Small => "small",
+            ShipSize::Medium => "medium",
+            ShipSize::Large => "large",
+            ShipSize::Xl => "xl",
+        }
+    }
+
+    /// Parses a size, or one of the short names people put in bookmarks (s, m, l, frig, capital, ...).
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        match tag.to_lowercase().as_str() {
+            "s" | "small" | "frig" | "frigate" => Some(ShipSize::Small),
+            "m" | "medium" => Some(ShipSize::Medium),
+            "l" | "large" => Some(ShipSize::Large),
+            "xl" | "capital" | "freighter" => Some(ShipSize::Xl),
+            _ => None,
+        }
+    }
+}
+
+/// How much mass a wormhole has left, as shown when it's looked at.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MassState {
+    /// More than half left
+    Fresh,
+    /// Less than half left
+    Reduced,
+    /// Less than a tenth left
+    Critical,
+}
+
+impl MassState {
+    pub const ALL: [MassState; 3] = [MassState::Fresh, MassState::Reduced, MassState::Critical];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MassState::Fresh => "fresh",
+            MassState::Reduced => "reduced",
+            MassState::Critical => "critical",
+        }
+    }
+
+    /// Parses a mass state, or one of the short names people put in bookmarks (stable, half, crit, ...).
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        match tag.to_lowercase().as_str() {
+            "fresh" | "stable" => Some(MassState::Fresh),
+            "reduced" | "half" | "destab" | "destabilized" => Some(MassState::Reduced),
+            "critical" | "crit" => Some(MassState::Critical),
+            _ => None,
+        }
+    }
+}
+
+/// Everything known about one side of a wormhole. Anything that isn't known is assumed to be fine.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WormholeInfo {
+    /// The bookmark to warp to in the system the jump starts in, like `ABC-123`
+    pub bookmark: Option<String>,
+    /// The bookmark on the other end, in the system the jump leads to, when this end wasn't bookmarked
+    pub far_bookmark: Option<String>,
+    /// The signature to scan down in the system the jump starts in, like `ABC-123`
+    pub signature: Option<String>,
+    /// The wormhole type, like `Q063`
+    pub wh_type: Option<String>,
+    /// The biggest ships it lets through
+    pub size: Option<ShipSize>,
+    pub expires: Option<SystemTime>,
+    /// Tagged as end of life, without a time
+    pub eol: bool,
+    pub mass: Option<MassState>,
+    /// The file it was loaded from
+    pub source: Option<String>,
+}
+
+impl WormholeInfo {
+    /// Fills in whatever a bookmark tag like `L`, `EOL`, or `crit` says. Returns false for tags that mean nothing.
+    pub fn apply_tag(&mut self, tag: &str) -> bool {
+        let tag = tag.trim_matches(|c: char| !c.is_alphanumeric());
+
+        if tag.eq_ignore_ascii_case("eol") {
+            self.eol = true;
+        } else if let Some(size) = ShipSize::from_tag(tag) {
+            self.size = Some(size);
+        } else if let Some(mass) = MassState::from_tag(tag) {
+            self.mass = Some(mass);
+        } else {
+            return false;
+        }
+
+        true
+    }
+
+    pub fn is_known(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// Fills in whatever `other` knows about the same hole that this doesn't. Where both have an expiry time the
+    /// sooner one is kept, and either being end of life makes it end of life.
+    pub fn merge(&mut self, other: WormholeInfo) {
+        self.bookmark = self.bookmark.take().or(other.bookmark);
+        self.far_bookmark = self.far_bookmark.take().or(other.far_bookmark);
+        self.signature = self.signature.take().or(other.signature);
+        self.wh_type = self.wh_type.take().or(other.wh_type);
+        self.size = self.size.or(other.size);
+        self.expires = self.expires.into_iter().chain(other.expires).min();
+        self.eol |= other.eol;
+        self.mass = self.mass.or(other.mass);
+        self.source = self.source.take().or(other.source);
+    }
+
+    pub fn fits(&self, ship: ShipSize) -> bool {
+        self.size.is_none_or(|size| ship <= size)
+    }
+
+    /// The time it has left at `now`, if it has an expiry time.
+    pub fn get_remaining_life(&self, now: SystemTime) -> Option<Duration> {
+        self.expires.map(|expires| expires.duration_since(now).unwrap_or_default())
+    }
+
+    /// Whether it's tagged as end of life, or expires within [`END_OF_LIFE`] of `now`.
+    pub fn is_end_of_life(&self, now: SystemTime) -> bool {
+        self.eol || self.get_remaining_life(now).is_some_and(|left| left <= END_OF_LIFE)
+    }
+
+    /// Like "warp to bookmark ABC-123, large, mass critical, 1h 20m left", or "sig ABC-123, Q063, ..." when there's
+    /// a signature to scan down instead.
+    pub fn describe(&self, now: SystemTime) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(bookmark) = &self.bookmark {
+            parts.push(format!("warp to bookmark {bookmark}"));
+        }
+
+        if let Some(bookmark) = &self.far_bookmark {
+            parts.push(format!("bookmarked as {bookmark} on the far side"));
+        }
+
+        if let Some(signature) = &self.signature {
+            parts.push(format!("sig {signature}"));
+        }
+
+        if let Some(wh_type) = &self.wh_type {
+            parts.push(wh_type.clone());
+        }
+
+        if let Some(size) = self.size {
+            parts.push(size.name().to_owned());
+        }
+
+        if let Some(mass) = self.mass {
+            parts.push(format!("mass {}", mass.name()));
+        }
+
+        match self.get_remaining_life(now) {
+            Some(left) => parts.push(format!("{} left", fatigue::format_duration(left.as_secs_f64() / 60.0))),
+            None if self.eol => parts.push("end of life".to_owned()),
+            None => {},
+        }
+
+        parts.join(", ")
+    }
+
+    /// What's risky about taking it, like "end of life" or "mass critical".
+    pub fn get_risks(&self, now: SystemTime) -> Vec<&'static str> {
+        let mut risks = Vec::new();
+
+        if self.is_end_of_life(now) {
+            risks.push("end of life");
+        }
+
+        if self.mass == Some(MassState::Critical) {
+            risks.push("mass critical");
+        }
+
+        risks
+    }
+}
+
+/// Which wormholes routes may take.
+#[derive(Debug, Clone)]
+pub struct WormholeFilter {
+    /// Holes too small for this are left out
+    pub ship_size: Option<ShipSize>,
+    /// Holes that expire sooner than this are left out, on top of the ones that have already expired. When it isn't
+    /// zero, holes tagged as end of life without a time are left out too.
+    pub min_life: Duration,
+    /// The time expiry is measured from
+    pub now: SystemTime,
+}
+
+impl Default for WormholeFilter {
+    fn default() -> Self {
+        Self {
+            ship_size: None,
+            min_life: Duration::ZERO,
+            now: SystemTime::now(),
+        }
+    }
+}
+
+impl WormholeFilter {
+    pub fn is_allowed(&self, info: &WormholeInfo) -> bool {
+        if self.ship_size.is_some_and(|ship| !info.fits(ship)) {
+            return false;
+        }
+
+        match info.get_remaining_life(self.now) {
+            // a hole that has collapsed is never allowed, however little life is asked for
+            Some(left) => !left.is_zero() && left >= self.min_life,
+            None => self.min_life.is_zero() || !info.eol,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_fill_in_what_they_say() {
+        let mut info = WormholeInfo::default();
+
+        for tag in ["L", "(EOL)", "crit"] {
+            assert!(info.apply_tag(tag));
+        }
+
+        assert!(!info.apply_tag("(NS)"));
+
+        assert_eq!(info, WormholeInfo { size: Some(ShipSize::Large), eol: true, mass: Some(MassState::Critical), ..Default::default() });
+        assert_eq!(info.describe(SystemTime::UNIX_EPOCH), "large, mass critical, end of life");
+    }
+
+    #[test]
+    fn small_holes_and_short_lives_are_filtered() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+        let medium = WormholeInfo { size: Some(ShipSize::Medium), ..Default::default() };
+        let dying = WormholeInfo { expires: Some(now + Duration::from_secs(600)), ..Default::default() };
+        let eol = WormholeInfo { eol: true, ..Default::default() };
+
+        let battleship = WormholeFilter { ship_size: Some(ShipSize::Large), min_life: Duration::ZERO, now };
+
+        assert!(!battleship.is_allowed(&medium));
+        assert!(battleship.is_allowed(&dying));
+        assert!(battleship.is_allowed(&WormholeInfo::default()));
+        assert!(battleship.is_allowed(&eol));
+
+        let collapsed = WormholeInfo { expires: Some(now), ..Default::default() };
+
+        assert!(!battleship.is_allowed(&collapsed));
+        assert!(!WormholeFilter { now, ..Default::default() }.is_allowed(&WormholeInfo { expires: Some(now - Duration::from_secs(1)), ..Default::default() }));
+
+        let careful = WormholeFilter { ship_size: Some(ShipSize::Small), min_life: Duration::from_secs(1800), now };
+
+        assert!(careful.is_allowed(&medium));
+        assert!(!careful.is_allowed(&dying));
+        assert!(!careful.is_allowed(&eol));
+
+        assert!(dying.is_end_of_life(now));
+        assert_eq!(dying.get_risks(now), vec!["end of life"]);
+        assert_eq!(dying.describe(now), "10m 00s left");
+    }
+
+    #[test]
+    fn merging_fills_in_what_is_missing() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+        let mut info = WormholeInfo { signature: Some("ABC-123".to_owned()), expires: Some(now + Duration::from_secs(3600)), source: Some("chain.json".to_owned()), ..Default::default() };
+
+        info.merge(WormholeInfo {
+            signature: Some("XYZ-789".to_owned()),
+            size: Some(ShipSize::Medium),
+            expires: Some(now + Duration::from_secs(600)),
+            mass: Some(MassState::Reduced),
+            source: Some("scout.json".to_owned()),
+            ..Default::default()
+        });
+
+        assert_eq!(info, WormholeInfo {
+            signature: Some("ABC-123".to_owned()),
+            size: Some(ShipSize::Medium),
+            expires: Some(now + Duration::from_secs(600)),
+            mass: Some(MassState::Reduced),
+            source: Some("chain.json".to_owned()),
+            ..Default::default()
+        });
+
+        info.merge(WormholeInfo { eol: true, ..Default::default() });
+
+        assert!(info.eol);
+        assert_eq!(info.expires, Some(now + Duration::from_secs(600)));
+    }
+}
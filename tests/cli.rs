@@ -0,0 +1,62 @@
+use std::{io::Write, path::{Path, PathBuf}, process::{Command, Output}};
+
+/// Zips a fixture SDE directory into `dir`, so it can be passed to `--sde`.
+fn zip_fixture(name: &str, dir: &Path) -> PathBuf {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name);
+    let path = dir.join(format!("{name}.zip"));
+
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+
+    for entry in std::fs::read_dir(&root).unwrap() {
+        let file = entry.unwrap().path();
+
+        zip.start_file(file.file_name().unwrap().to_str().unwrap(), zip::write::FileOptions::default()).unwrap();
+        zip.write_all(&std::fs::read(&file).unwrap()).unwrap();
+    }
+
+    zip.finish().unwrap();
+
+    path
+}
+
+/// Runs yaerp against the jsonl fixture SDE, with a cache directory of its own.
+fn run_with_fixture(test: &str, args: &[&str]) -> Output {
+    let dir = std::env::temp_dir().join(format!("yaerp-cli-{test}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let sde = zip_fixture("sde-jsonl", &dir);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_yaerp"))
+        .arg("--sde")
+        .arg(&sde)
+        .args(args)
+        .env("YAERP_CACHE_DIR", dir.join("cache"))
+        .output()
+        .unwrap();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    output
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "yaerp failed: {}", String::from_utf8_lossy(&output.stderr));
+
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn nearest_where_names_come_from_the_sde() {
+    let output = run_with_fixture("nearest-where", &["nearest", "Tanoo", "--where", "region=The Forge"]);
+
+    assert!(stdout(&output).starts_with("Nearest match: Jita"));
+}
+
+#[test]
+fn removed_jump_names_come_from_the_sde() {
+    let output = run_with_fixture("remove-jump", &["--format", "json", "--remove-jump", "Jita -> Lashesih", "Tanoo", "Jita"]);
+
+    let report: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+
+    assert_eq!(report["total_jumps"], 2);
+}